@@ -0,0 +1,741 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::*;
+
+/// How a field behaves over time, which decides how a window of its values is aggregated
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// A level that goes up and down, e.g. memory in use or a temperature
+    Gauge,
+    /// An amount per second, e.g. network speed. Aggregated like a gauge, so that the mean times
+    /// the length of the window is the amount over the whole window.
+    Rate,
+    /// Doesn't change while the app runs, e.g. total memory. The latest value is kept.
+    Constant,
+    /// Only goes up, e.g. errors since the interface came up. Its mean (and any percentile) is the
+    /// latest value, as a mean of a running total means nothing.
+    Counter,
+}
+
+/// What a window of values is reduced to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Statistic {
+    Mean,
+    Min,
+    Max,
+    /// Nearest-rank percentile, from 0 to 100
+    Percentile(f64),
+    /// Values added up, to roll up samples as they come without keeping them. Counters and
+    /// constants keep the latest value.
+    Sum,
+    /// How many values there are, leaving out missing ones
+    Count,
+    /// The mean of a window of two: the [`Statistic::Sum`] of some values and their
+    /// [`Statistic::Count`]
+    MeanOfSum,
+}
+
+/// Reduces a window of samples to a single one, field by field according to their [`Kind`].
+///
+/// Labels, such as names, are taken from the latest sample. Vectors are aggregated element-wise,
+/// matching elements by their [`Key`].
+pub trait Aggregate: Sized {
+    /// `window` is oldest first and must not be empty
+    fn aggregate(window: &[&Self], statistic: Statistic) -> Self {
+        let window: Vec<_> = window.iter().map(|value| (*value, 1.0)).collect();
+        Self::aggregate_weighted(&window, statistic)
+    }
+
+    /// Like [`Aggregate::aggregate`], with a weight for every sample that [`Statistic::Mean`]
+    /// takes into account, e.g. the number of samples a rollup's mean is over
+    fn aggregate_weighted(window: &[(&Self, f64)], statistic: Statistic) -> Self;
+
+    /// Clears the labels, except for those elements are told apart by, e.g. to keep a lot of
+    /// rollups in less memory
+    fn clear_labels(&mut self);
+}
+
+/// A single number in a sample
+trait Value: Copy {
+    fn to_f64(self) -> f64;
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Value for u32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    /// Rounded, so that means aren't truncated
+    fn from_f64(value: f64) -> Self {
+        value.round() as Self
+    }
+}
+
+impl Value for u64 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn from_f64(value: f64) -> Self {
+        value.round() as Self
+    }
+}
+
+impl Value for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn from_f64(value: f64) -> Self {
+        value as Self
+    }
+}
+
+impl Value for Duration {
+    fn to_f64(self) -> f64 {
+        self.as_nanos() as f64
+    }
+    fn from_f64(value: f64) -> Self {
+        Duration::from_nanos(value.round() as u64)
+    }
+}
+
+/// Reduces values along with their weights, which only [`Statistic::Mean`] takes into account
+fn reduce(window: Vec<(f64, f64)>, kind: Kind, statistic: Statistic) -> f64 {
+    let latest = window[window.len() - 1].0;
+    let values = window.iter().map(|(value, _)| *value);
+    match (kind, statistic) {
+        (_, Statistic::Count) => window.len() as f64,
+        (Kind::Constant | Kind::Counter, Statistic::MeanOfSum) => window[0].0,
+        (_, Statistic::MeanOfSum) => window[0].0 / window[1].0,
+        (Kind::Constant, _)
+        | (Kind::Counter, Statistic::Mean | Statistic::Percentile(_) | Statistic::Sum) => latest,
+        (_, Statistic::Min) => values.fold(f64::INFINITY, f64::min),
+        (_, Statistic::Max) => values.fold(f64::NEG_INFINITY, f64::max),
+        (_, Statistic::Mean) => {
+            let weights = window.iter().map(|(_, weight)| weight).sum::<f64>();
+            window
+                .iter()
+                .map(|(value, weight)| value * weight)
+                .sum::<f64>()
+                / weights
+        }
+        (_, Statistic::Sum) => values.sum(),
+        (_, Statistic::Percentile(percentile)) => {
+            let mut values: Vec<_> = values.collect();
+            values.sort_by(f64::total_cmp);
+            let rank = (percentile / 100.0 * values.len() as f64).ceil() as usize;
+            values[rank.clamp(1, values.len()) - 1]
+        }
+    }
+}
+
+/// A field holding a number, aggregated according to its [`Kind`]
+trait Field: Sized {
+    fn aggregate_field(window: Vec<(Self, f64)>, kind: Kind, statistic: Statistic) -> Self;
+}
+
+impl<T: Value> Field for T {
+    fn aggregate_field(window: Vec<(Self, f64)>, kind: Kind, statistic: Statistic) -> Self {
+        let values = window
+            .into_iter()
+            .map(|(value, weight)| (value.to_f64(), weight))
+            .collect();
+        T::from_f64(reduce(values, kind, statistic))
+    }
+}
+
+/// Missing values are left out, `None` if all of them are missing
+impl<T: Value> Field for Option<T> {
+    fn aggregate_field(window: Vec<(Self, f64)>, kind: Kind, statistic: Statistic) -> Self {
+        let values: Vec<_> = window
+            .into_iter()
+            .filter_map(|(value, weight)| Some((value?.to_f64(), weight)))
+            .collect();
+        (!values.is_empty()).then(|| T::from_f64(reduce(values, kind, statistic)))
+    }
+}
+
+/// Elements are matched by their [`Key`], so that a disk or an interface coming or going doesn't
+/// mix up the others. Every element is aggregated over the samples it's in, in the order of the
+/// latest sample followed by the ones gone by then.
+impl<T: Aggregate + Key> Aggregate for Vec<T> {
+    fn aggregate_weighted(window: &[(&Self, f64)], statistic: Statistic) -> Self {
+        let mut keys = Vec::new();
+        let mut elements: HashMap<String, Vec<(&T, f64)>> = HashMap::new();
+        for &(values, weight) in window {
+            for (i, value) in values.iter().enumerate() {
+                let key = value.key(i);
+                elements
+                    .entry(key)
+                    .or_insert_with_key(|key| {
+                        keys.push(key.clone());
+                        Vec::new()
+                    })
+                    .push((value, weight));
+            }
+        }
+        let latest = window
+            .last()
+            .map_or(&[][..], |(values, _)| values.as_slice());
+        let latest: Vec<_> = latest
+            .iter()
+            .enumerate()
+            .map(|(i, value)| value.key(i))
+            .collect();
+        keys.retain(|key| !latest.contains(key));
+        latest
+            .into_iter()
+            .chain(keys)
+            .filter_map(|key| Some(T::aggregate_weighted(&elements.remove(&key)?, statistic)))
+            .collect()
+    }
+
+    fn clear_labels(&mut self) {
+        self.iter_mut().for_each(T::clear_labels);
+    }
+}
+
+macro_rules! aggregate_field {
+    (Label, $window:ident, $field:ident, $statistic:ident) => {
+        $window[$window.len() - 1].0.$field.clone()
+    };
+    (Key, $window:ident, $field:ident, $statistic:ident) => {
+        aggregate_field!(Label, $window, $field, $statistic)
+    };
+    (Nested, $window:ident, $field:ident, $statistic:ident) => {
+        Aggregate::aggregate_weighted(
+            &$window
+                .iter()
+                .map(|(value, weight)| (&value.$field, *weight))
+                .collect::<Vec<_>>(),
+            $statistic,
+        )
+    };
+    ($kind:ident, $window:ident, $field:ident, $statistic:ident) => {
+        Field::aggregate_field(
+            $window
+                .iter()
+                .map(|(value, weight)| (value.$field, *weight))
+                .collect(),
+            Kind::$kind,
+            $statistic,
+        )
+    };
+}
+
+macro_rules! clear_field {
+    (Label, $value:ident, $field:ident) => {
+        $value.$field = Default::default()
+    };
+    (Nested, $value:ident, $field:ident) => {
+        Aggregate::clear_labels(&mut $value.$field)
+    };
+    ($kind:ident, $value:ident, $field:ident) => {};
+}
+
+macro_rules! column_field {
+    (Label, $value:ident, $field:ident, $name:expr, $prefix:ident, $column:ident) => {};
+    (Key, $value:ident, $field:ident, $name:expr, $prefix:ident, $column:ident) => {};
+    (Nested, $value:ident, $field:ident, $name:expr, $prefix:ident, $column:ident) => {
+        Columns::columns(&$value.$field, &join($prefix, $name), $column)
+    };
+    ($kind:ident, $value:ident, $field:ident, $name:expr, $prefix:ident, $column:ident) => {
+        if let Some(number) = ToNumber::to_number(&$value.$field) {
+            $column(join($prefix, $name), Kind::$kind, number);
+        }
+    };
+}
+
+macro_rules! column_name {
+    ($field:ident) => {
+        stringify!($field)
+    };
+    ($field:ident $name:ident) => {
+        stringify!($name)
+    };
+}
+
+/// Implements [`Aggregate`] and [`Columns`] for a struct, given the [`Kind`] of every field.
+///
+/// Fields can also be a `Label`, taken from the latest sample, a `Key` label that is part of the
+/// element's [`Key`], or `Nested` to aggregate them on their own. Every field has to be listed, so
+/// that new ones can't be left out by accident.
+/// `field as name` names the field's columns differently.
+macro_rules! impl_aggregate {
+    ($type:ident { $($field:ident $(as $name:ident)?: $kind:ident),* $(,)? }) => {
+        impl Aggregate for $type {
+            fn aggregate_weighted(window: &[(&Self, f64)], statistic: Statistic) -> Self {
+                Self {
+                    $($field: aggregate_field!($kind, window, $field, statistic),)*
+                }
+            }
+
+            fn clear_labels(&mut self) {
+                $(clear_field!($kind, self, $field);)*
+            }
+        }
+
+        impl Columns for $type {
+            fn columns(&self, prefix: &str, column: &mut dyn FnMut(String, Kind, Number)) {
+                $(column_field!(
+                    $kind,
+                    self,
+                    $field,
+                    column_name!($field $($name)?),
+                    prefix,
+                    column
+                );)*
+            }
+        }
+    };
+}
+
+impl_aggregate!(SystemUtilization {
+    // The means are the middle of the window
+    timestamp: Gauge,
+    monotonic: Gauge,
+    cpus as cpu: Nested,
+    cpu_usage: Gauge,
+    cpu_times: Nested,
+    mem: Gauge,
+    mem_max: Constant,
+    memory: Nested,
+    disk: Nested,
+    disks as disk: Nested,
+    gpus as gpu: Nested,
+    up_time: Counter,
+    processes: Gauge,
+    network: Nested,
+    sensors as sensor: Nested,
+});
+impl_aggregate!(CpuCore {
+    usage: Gauge,
+    freq: Gauge,
+    times: Nested,
+});
+impl_aggregate!(CpuTimes {
+    user: Gauge,
+    nice: Gauge,
+    system: Gauge,
+    idle: Gauge,
+    iowait: Gauge,
+    irq: Gauge,
+    softirq: Gauge,
+    steal: Gauge,
+});
+impl_aggregate!(Gpu {
+    usage: Gauge,
+    mem_busy: Gauge,
+    vram_total: Constant,
+    vram_used: Gauge,
+    temp: Gauge,
+    power: Gauge,
+    power_limit: Constant,
+    graphics_clock: Gauge,
+    memory_clock: Gauge,
+    fan: Gauge,
+    pcie_rx: Rate,
+    pcie_tx: Rate,
+    encoder: Gauge,
+    decoder: Gauge,
+    throttle_reasons: Label,
+});
+impl_aggregate!(Memory {
+    available: Gauge,
+    free: Gauge,
+    cached: Gauge,
+    buffers: Gauge,
+    shared: Gauge,
+    swap_used: Gauge,
+    swap_total: Constant,
+    swap_in: Rate,
+    swap_out: Rate,
+});
+impl_aggregate!(Disk {
+    read_bytes: Rate,
+    writen_bytes: Rate,
+});
+impl_aggregate!(DiskDevice {
+    name: Key,
+    read_bytes: Rate,
+    written_bytes: Rate,
+    iops: Rate,
+    busy: Gauge,
+    latency: Gauge,
+});
+impl_aggregate!(Sensor {
+    chip: Key,
+    device: Key,
+    label: Key,
+    temp: Gauge,
+    max: Constant,
+    critical: Constant,
+});
+impl_aggregate!(Network {
+    down: Rate,
+    up: Rate,
+    interfaces as interface: Nested,
+});
+impl_aggregate!(NetworkInterface {
+    name: Key,
+    mac: Label,
+    ips: Label,
+    included: Label,
+    down: Rate,
+    up: Rate,
+    packets_down: Rate,
+    packets_up: Rate,
+    errors_down: Counter,
+    errors_up: Counter,
+    drops_down: Counter,
+    drops_up: Counter,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+    use serde_json::Value as Json;
+
+    fn util(timestamp: u64, mem: u64, errors: u64, fan: Option<u32>) -> SystemUtilization {
+        SystemUtilization {
+            timestamp,
+            mem,
+            mem_max: timestamp,
+            gpus: vec![Gpu {
+                fan,
+                ..Default::default()
+            }],
+            network: Network {
+                interfaces: vec![NetworkInterface {
+                    name: "eth0".to_owned(),
+                    mac: timestamp.to_string(),
+                    errors_down: errors,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn kind_test() {
+        let window = [
+            util(1000, 1, 5, Some(40)),
+            util(2000, 2, 3, None),
+            util(3000, 4, 4, Some(60)),
+            util(4000, 3, 6, None),
+        ];
+        let window: Vec<_> = window.iter().collect();
+        let aggregate = |statistic| SystemUtilization::aggregate(&window, statistic);
+
+        let mean = aggregate(Statistic::Mean);
+        assert_eq!(2500, mean.timestamp);
+        // 2.5 is rounded rather than truncated
+        assert_eq!(3, mean.mem);
+        assert_eq!(4000, mean.mem_max);
+        assert_eq!(6, mean.network.interfaces[0].errors_down);
+        assert_eq!("4000", mean.network.interfaces[0].mac);
+        assert_eq!(Some(50), mean.gpus[0].fan);
+
+        let min = aggregate(Statistic::Min);
+        assert_eq!(1000, min.timestamp);
+        assert_eq!(1, min.mem);
+        assert_eq!(4000, min.mem_max);
+        assert_eq!(3, min.network.interfaces[0].errors_down);
+        assert_eq!(Some(40), min.gpus[0].fan);
+
+        let max = aggregate(Statistic::Max);
+        assert_eq!(4, max.mem);
+        assert_eq!(6, max.network.interfaces[0].errors_down);
+
+        assert_eq!(2, aggregate(Statistic::Percentile(50.0)).mem);
+        assert_eq!(3, aggregate(Statistic::Percentile(75.0)).mem);
+        assert_eq!(1, aggregate(Statistic::Percentile(0.0)).mem);
+        assert_eq!(4, aggregate(Statistic::Percentile(100.0)).mem);
+        assert_eq!(
+            6,
+            aggregate(Statistic::Percentile(50.0)).network.interfaces[0].errors_down
+        );
+    }
+
+    #[test]
+    fn gpu_test() {
+        let gpu = |usage, fan, throttle_reasons: &[&str]| Gpu {
+            usage,
+            power: usage as f32,
+            fan,
+            throttle_reasons: throttle_reasons.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let window = [&gpu(10, Some(40), &["thermal"]), &gpu(30, Some(60), &[])];
+        let mean = Gpu::aggregate(&window, Statistic::Mean);
+        assert_eq!(20, mean.usage);
+        assert_eq!(20.0, mean.power);
+        assert_eq!(Some(50), mean.fan);
+        assert!(mean.throttle_reasons.is_empty());
+
+        let window = [&gpu(10, None, &[]), &gpu(30, None, &["power cap"])];
+        let mean = Gpu::aggregate(&window, Statistic::Mean);
+        assert_eq!(None, mean.fan);
+        assert_eq!(vec!["power cap"], mean.throttle_reasons);
+    }
+
+    #[test]
+    fn vec_test() {
+        let mut short = util(1000, 1, 0, None);
+        short.gpus.clear();
+        let window = [&util(2000, 2, 0, Some(10)), &short];
+        let mean = SystemUtilization::aggregate(&window, Statistic::Mean);
+        assert_eq!(Some(10), mean.gpus[0].fan);
+
+        let interface = |name: &str, down| NetworkInterface {
+            name: name.to_owned(),
+            down,
+            ..Default::default()
+        };
+        let window = [
+            vec![interface("eth0", 10), interface("wlan0", 20)],
+            vec![interface("tun0", 100), interface("eth0", 30)],
+            vec![interface("tun0", 200), interface("eth0", 50)],
+        ];
+        let window: Vec<_> = window.iter().collect();
+        let mean = Vec::aggregate(&window, Statistic::Mean);
+        let mean: Vec<_> = mean.iter().map(|i| (i.name.as_str(), i.down)).collect();
+        assert_eq!(vec![("tun0", 150), ("eth0", 30), ("wlan0", 20)], mean);
+    }
+
+    #[test]
+    fn clear_labels_test() {
+        let mut sample = util(1000, 1, 0, None);
+        sample.gpus[0].throttle_reasons = vec!["thermal".to_owned()];
+        sample.sensors = vec![Sensor {
+            chip: "nvme".to_owned(),
+            device: "nvme0".to_owned(),
+            label: "Composite".to_owned(),
+            ..Default::default()
+        }];
+        sample.clear_labels();
+        assert!(sample.gpus[0].throttle_reasons.is_empty());
+        let interface = &sample.network.interfaces[0];
+        assert_eq!(
+            ("eth0", ""),
+            (interface.name.as_str(), interface.mac.as_str())
+        );
+        assert_eq!("nvme.nvme0.Composite", sample.sensors[0].key(0));
+    }
+
+    /// Every number in a sample along with its path, durations count as a single number
+    fn push_numbers(json: &Json, path: String, numbers: &mut Vec<(String, f64)>) {
+        match json {
+            Json::Number(number) => numbers.push((path, number.as_f64().unwrap())),
+            Json::Object(object) if object.contains_key("nanos") => {
+                let secs = object["secs"].as_f64().unwrap();
+                let nanos = object["nanos"].as_f64().unwrap();
+                numbers.push((path, secs * 1e9 + nanos));
+            }
+            Json::Object(object) => {
+                for (key, value) in object {
+                    push_numbers(value, format!("{path}.{key}"), numbers);
+                }
+            }
+            Json::Array(array) => {
+                for (i, value) in array.iter().enumerate() {
+                    push_numbers(value, format!("{path}.{i}"), numbers);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn sample_numbers(sample: &SystemUtilization) -> Vec<(String, f64)> {
+        let mut result = Vec::new();
+        push_numbers(
+            &serde_json::to_value(sample).unwrap(),
+            String::new(),
+            &mut result,
+        );
+        result
+    }
+
+    fn number_count(json: &Json) -> usize {
+        match json {
+            Json::Number(_) => 1,
+            Json::Object(object) => object.values().map(number_count).sum(),
+            Json::Array(array) => array.iter().map(number_count).sum(),
+            _ => 0,
+        }
+    }
+
+    /// Replaces every number of `json` with one of `values`, integers with integers
+    fn fill(json: &mut Json, values: &mut impl Iterator<Item = f64>) {
+        match json {
+            Json::Number(number) if number.is_f64() => {
+                *json = Json::from(values.next().unwrap());
+            }
+            Json::Number(_) => *json = Json::from(values.next().unwrap() as u64),
+            Json::Object(object) => object.values_mut().for_each(|value| fill(value, values)),
+            Json::Array(array) => array.iter_mut().for_each(|value| fill(value, values)),
+            _ => {}
+        }
+    }
+
+    /// A sample with every field set, so that any new field shows up in its JSON
+    fn template() -> Json {
+        let sample = SystemUtilization {
+            cpus: vec![CpuCore::default(); 2],
+            disks: vec![DiskDevice::default()],
+            gpus: vec![Gpu {
+                fan: Some(0),
+                ..Default::default()
+            }],
+            network: Network {
+                interfaces: vec![NetworkInterface::default()],
+                ..Default::default()
+            },
+            sensors: vec![Sensor {
+                max: Some(0.0),
+                critical: Some(0.0),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        serde_json::to_value(sample).unwrap()
+    }
+
+    fn window() -> impl Strategy<Value = Vec<SystemUtilization>> {
+        let count = number_count(&template());
+        let sample = prop::collection::vec(0.0..1000.0_f64, count).prop_map(|values| {
+            let mut json = template();
+            fill(&mut json, &mut values.into_iter());
+            serde_json::from_value::<SystemUtilization>(json).unwrap()
+        });
+        prop::collection::vec(sample, 1..20)
+    }
+
+    /// Whether `value` is between `low` and `high`, give or take float rounding
+    fn within(value: f64, low: f64, high: f64) -> bool {
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-3 * a.abs().max(b.abs()).max(1.0);
+        (low <= value || close(low, value)) && (value <= high || close(high, value))
+    }
+
+    fn statistics(percentile: f64) -> [Statistic; 4] {
+        [
+            Statistic::Mean,
+            Statistic::Min,
+            Statistic::Max,
+            Statistic::Percentile(percentile),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn aggregate_within_window_test(window in window(), percentile in 0.0..=100.0_f64) {
+            let samples: Vec<_> = window.iter().map(sample_numbers).collect();
+            let window: Vec<_> = window.iter().collect();
+            for statistic in statistics(percentile) {
+                let aggregate = SystemUtilization::aggregate(&window, statistic);
+                let aggregate = sample_numbers(&aggregate);
+                prop_assert_eq!(samples[0].len(), aggregate.len());
+                for (i, (path, value)) in aggregate.iter().enumerate() {
+                    let values = samples.iter().map(|sample| sample[i].1);
+                    let low = values.clone().fold(f64::INFINITY, f64::min);
+                    let high = values.fold(f64::NEG_INFINITY, f64::max);
+                    prop_assert!(
+                        within(*value, low, high),
+                        "{} of {:?} is {}, out of {}..{}", path, statistic, value, low, high
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn aggregate_order_test(window in window(), percentile in 0.0..=100.0_f64) {
+            let window: Vec<_> = window.iter().collect();
+            let aggregate =
+                |statistic| sample_numbers(&SystemUtilization::aggregate(&window, statistic));
+            let min = aggregate(Statistic::Min);
+            let max = aggregate(Statistic::Max);
+            for statistic in [Statistic::Mean, Statistic::Percentile(percentile)] {
+                let values = aggregate(statistic);
+                for (i, (path, value)) in values.iter().enumerate() {
+                    let (low, high) = (min[i].1, max[i].1);
+                    prop_assert!(
+                        within(*value, low, high),
+                        "{} of {:?} is {}, out of {}..{}", path, statistic, value, low, high
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn aggregate_device_inserted_test(
+            window in window(),
+            at in 0..20_usize,
+            percentile in 0.0..=100.0_f64,
+        ) {
+            // A disk plugged in partway through the window, listed before the one already there
+            let mut window = window;
+            let at = at % window.len();
+            for (i, sample) in window.iter_mut().enumerate() {
+                sample.disks[0].name = "sda".to_owned();
+                if i >= at {
+                    let mut disk = sample.disks[0].clone();
+                    disk.name = "sdb".to_owned();
+                    disk.read_bytes += 1000;
+                    sample.disks.insert(0, disk);
+                }
+            }
+            let device = |name: &str, statistic| {
+                let disks: Vec<_> = window
+                    .iter()
+                    .flat_map(|sample| sample.disks.iter().filter(|disk| disk.name == name))
+                    .collect();
+                serde_json::to_value(DiskDevice::aggregate(&disks, statistic)).unwrap()
+            };
+            let window: Vec<_> = window.iter().collect();
+            for statistic in statistics(percentile) {
+                let disks = SystemUtilization::aggregate(&window, statistic).disks;
+                prop_assert_eq!(2, disks.len());
+                prop_assert_eq!(device("sdb", statistic), serde_json::to_value(&disks[0]).unwrap());
+                prop_assert_eq!(device("sda", statistic), serde_json::to_value(&disks[1]).unwrap());
+            }
+        }
+
+        #[test]
+        fn aggregate_running_mean_test(window in window()) {
+            let mean = sample_numbers(&SystemUtilization::aggregate(
+                &window.iter().collect::<Vec<_>>(),
+                Statistic::Mean,
+            ));
+            let count = |sample| SystemUtilization::aggregate(&[sample], Statistic::Count);
+            let (mut sum, mut counts) = (window[0].clone(), count(&window[0]));
+            for sample in &window[1..] {
+                sum = SystemUtilization::aggregate(&[&sum, sample], Statistic::Sum);
+                counts = SystemUtilization::aggregate(&[&counts, &count(sample)], Statistic::Sum);
+            }
+            let running = SystemUtilization::aggregate(&[&sum, &counts], Statistic::MeanOfSum);
+            let running = sample_numbers(&running);
+            prop_assert_eq!(mean.len(), running.len());
+            for ((path, expected), (_, value)) in mean.iter().zip(&running) {
+                prop_assert!(
+                    within(*value, *expected, *expected),
+                    "{} is {}, the mean is {}", path, value, expected
+                );
+            }
+        }
+
+        #[test]
+        fn aggregate_single_sample_test(window in window()) {
+            let sample = &window[0];
+            for statistic in statistics(50.0) {
+                let aggregate = SystemUtilization::aggregate(&[sample], statistic);
+                prop_assert_eq!(
+                    serde_json::to_value(sample).unwrap(),
+                    serde_json::to_value(aggregate).unwrap()
+                );
+            }
+        }
+    }
+}
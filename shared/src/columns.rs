@@ -0,0 +1,229 @@
+use std::{fmt, time::Duration};
+
+use serde::Serialize;
+
+use crate::*;
+
+/// A single number of a sample, as it's exported
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Number {
+    Integer(u64),
+    Float(f32),
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Integer(value) => value.fmt(f),
+            Number::Float(value) => value.fmt(f),
+        }
+    }
+}
+
+/// Flattens a sample into named numbers, e.g. `cpu.3.usage` or `gpu.0.temp`.
+///
+/// Disks, network interfaces and sensors go by their name rather than their index, so that their
+/// columns stay the same when hardware comes and goes. Labels aren't columns and missing values are
+/// left out.
+pub trait Columns {
+    /// Calls `column` with the name, [`Kind`] and value of every number, prefixed with `prefix`
+    fn columns(&self, prefix: &str, column: &mut dyn FnMut(String, Kind, Number));
+}
+
+/// Name of a column under `prefix`
+pub(crate) fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{prefix}.{name}")
+    }
+}
+
+pub(crate) trait ToNumber {
+    fn to_number(&self) -> Option<Number>;
+}
+
+impl ToNumber for u32 {
+    fn to_number(&self) -> Option<Number> {
+        Some(Number::Integer(*self as u64))
+    }
+}
+
+impl ToNumber for u64 {
+    fn to_number(&self) -> Option<Number> {
+        Some(Number::Integer(*self))
+    }
+}
+
+impl ToNumber for f32 {
+    fn to_number(&self) -> Option<Number> {
+        Some(Number::Float(*self))
+    }
+}
+
+/// In seconds
+impl ToNumber for Duration {
+    fn to_number(&self) -> Option<Number> {
+        Some(Number::Integer(self.as_secs()))
+    }
+}
+
+impl<T: ToNumber> ToNumber for Option<T> {
+    fn to_number(&self) -> Option<Number> {
+        self.as_ref()?.to_number()
+    }
+}
+
+/// What an element of a list is called in column names, its index unless it has a name
+pub(crate) trait Key {
+    fn key(&self, index: usize) -> String {
+        index.to_string()
+    }
+}
+
+impl Key for CpuCore {}
+
+impl Key for Gpu {}
+
+impl Key for DiskDevice {
+    fn key(&self, _index: usize) -> String {
+        self.name.clone()
+    }
+}
+
+impl Key for NetworkInterface {
+    fn key(&self, _index: usize) -> String {
+        self.name.clone()
+    }
+}
+
+impl Key for Sensor {
+    /// `chip.device.label`, leaving out what's unknown
+    fn key(&self, _index: usize) -> String {
+        [&self.chip, &self.device, &self.label]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+impl<T: Columns + Key> Columns for Vec<T> {
+    fn columns(&self, prefix: &str, column: &mut dyn FnMut(String, Kind, Number)) {
+        for (i, element) in self.iter().enumerate() {
+            element.columns(&join(prefix, &element.key(i)), column);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(sample: &SystemUtilization) -> Vec<(String, Kind, Number)> {
+        let mut columns = Vec::new();
+        sample.columns("", &mut |name, kind, value| {
+            columns.push((name, kind, value))
+        });
+        columns
+    }
+
+    #[test]
+    fn columns_test() {
+        let sample = SystemUtilization {
+            timestamp: 1000,
+            cpus: vec![CpuCore::default(); 4],
+            up_time: Duration::from_millis(61_500),
+            disks: vec![DiskDevice {
+                name: "nvme0n1".to_owned(),
+                read_bytes: 4096,
+                ..Default::default()
+            }],
+            gpus: vec![Gpu {
+                temp: 60,
+                ..Default::default()
+            }],
+            sensors: vec![
+                Sensor {
+                    chip: "coretemp".to_owned(),
+                    label: "Package id 0".to_owned(),
+                    temp: 45.5,
+                    max: Some(100.0),
+                    critical: None,
+                    ..Default::default()
+                },
+                Sensor {
+                    chip: "nvme".to_owned(),
+                    device: "nvme1".to_owned(),
+                    label: "Composite".to_owned(),
+                    temp: 40.0,
+                    ..Default::default()
+                },
+            ],
+            network: Network {
+                interfaces: vec![NetworkInterface {
+                    name: "eth0".to_owned(),
+                    errors_down: 2,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let columns = columns(&sample);
+        let column = |name: &str| {
+            columns
+                .iter()
+                .find(|(column, _, _)| column == name)
+                .map(|(_, kind, value)| (*kind, *value))
+        };
+
+        assert_eq!("timestamp", columns[0].0);
+        assert_eq!(
+            Some((Kind::Gauge, Number::Float(0.0))),
+            column("cpu.3.usage")
+        );
+        assert_eq!(
+            Some((Kind::Gauge, Number::Float(0.0))),
+            column("cpu.3.times.user")
+        );
+        assert_eq!(
+            Some((Kind::Gauge, Number::Integer(60))),
+            column("gpu.0.temp")
+        );
+        assert_eq!(
+            Some((Kind::Rate, Number::Integer(4096))),
+            column("disk.nvme0n1.read_bytes")
+        );
+        assert_eq!(
+            Some((Kind::Gauge, Number::Float(45.5))),
+            column("sensor.coretemp.Package id 0.temp")
+        );
+        assert_eq!(
+            Some((Kind::Gauge, Number::Float(40.0))),
+            column("sensor.nvme.nvme1.Composite.temp")
+        );
+        assert_eq!(
+            Some((Kind::Counter, Number::Integer(2))),
+            column("network.interface.eth0.errors_down")
+        );
+        assert_eq!(
+            Some((Kind::Counter, Number::Integer(61))),
+            column("up_time")
+        );
+        // Missing values and labels aren't columns
+        assert_eq!(None, column("gpu.0.fan"));
+        assert_eq!(None, column("sensor.coretemp.Package id 0.critical"));
+        assert_eq!(None, column("disk.nvme0n1.name"));
+        assert_eq!(None, column("cpus.0.usage"));
+    }
+
+    #[test]
+    fn number_test() {
+        assert_eq!("4096", Number::Integer(4096).to_string());
+        assert_eq!("0.1", Number::Float(0.1).to_string());
+        assert_eq!("0.1", serde_json::to_string(&Number::Float(0.1)).unwrap());
+    }
+}
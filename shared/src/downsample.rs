@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Samples averaged over a bucket, with the lowest and highest value of every field
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Rollup {
+    pub min: SystemUtilization,
+    pub avg: SystemUtilization,
+    pub max: SystemUtilization,
+    /// How many samples the bucket holds
+    pub count: u32,
+}
+
+impl From<SystemUtilization> for Rollup {
+    /// A bucket holding a single sample
+    fn from(sample: SystemUtilization) -> Self {
+        Self {
+            min: sample.clone(),
+            avg: sample.clone(),
+            max: sample,
+            count: 1,
+        }
+    }
+}
+
+impl Rollup {
+    /// Rolls up a window of samples, oldest first
+    pub fn new(window: &[&SystemUtilization]) -> Self {
+        Self {
+            min: SystemUtilization::aggregate(window, Statistic::Min),
+            avg: SystemUtilization::aggregate(window, Statistic::Mean),
+            max: SystemUtilization::aggregate(window, Statistic::Max),
+            count: window.len() as u32,
+        }
+    }
+
+    /// Combines the rollups of consecutive buckets into one covering all of them. Means are
+    /// weighted by the number of samples, so that buckets cut short don't count as much.
+    pub fn merge(rollups: &[&Rollup]) -> Self {
+        let window = |field: fn(&Rollup) -> &SystemUtilization| -> Vec<_> {
+            rollups.iter().map(|rollup| field(rollup)).collect()
+        };
+        let averages: Vec<_> = rollups
+            .iter()
+            .map(|rollup| (&rollup.avg, rollup.count as f64))
+            .collect();
+        Self {
+            min: SystemUtilization::aggregate(&window(|rollup| &rollup.min), Statistic::Min),
+            avg: SystemUtilization::aggregate_weighted(&averages, Statistic::Mean),
+            max: SystemUtilization::aggregate(&window(|rollup| &rollup.max), Statistic::Max),
+            count: rollups.iter().map(|rollup| rollup.count).sum(),
+        }
+    }
+
+    /// See [`Aggregate::clear_labels`]
+    pub fn clear_labels(&mut self) {
+        for sample in [&mut self.min, &mut self.avg, &mut self.max] {
+            sample.clear_labels();
+        }
+    }
+
+    /// Whether sampling stopped for more than `max_interval` milliseconds between the end of this
+    /// bucket and the start of the `next` one, e.g. because the system was asleep or the app
+    /// wasn't running
+    pub fn gap_until(&self, next: &Rollup, max_interval: u64) -> bool {
+        // The monotonic clock starts over when the system boots
+        next.min
+            .monotonic
+            .checked_sub(self.max.monotonic)
+            .is_none_or(|elapsed| elapsed > max_interval)
+    }
+}
+
+/// Rolls up samples taken after `from` and up to `to` into buckets `resolution` milliseconds apart,
+/// oldest first. The samples must be sorted oldest first.
+pub fn downsample<'a>(
+    samples: impl DoubleEndedIterator<Item = &'a SystemUtilization>,
+    from: u64,
+    to: u64,
+    resolution: u64,
+) -> Vec<Rollup> {
+    buckets(samples, |sample| sample.timestamp, from, to, resolution)
+        .iter()
+        .map(|bucket| Rollup::new(bucket))
+        .collect()
+}
+
+/// Like [`downsample`], for rollups of shorter buckets
+pub fn downsample_rollups<'a>(
+    rollups: impl DoubleEndedIterator<Item = &'a Rollup>,
+    from: u64,
+    to: u64,
+    resolution: u64,
+) -> Vec<Rollup> {
+    buckets(rollups, |rollup| rollup.avg.timestamp, from, to, resolution)
+        .iter()
+        .map(|bucket| Rollup::merge(bucket))
+        .collect()
+}
+
+/// Groups items taken after `from` and up to `to`, oldest first, into buckets `resolution`
+/// milliseconds apart.
+///
+/// Buckets are centered on `to`, so jitter in the sampling time doesn't leave buckets empty.
+/// Empty buckets, e.g. from before the app started, are left out.
+fn buckets<'a, T>(
+    items: impl DoubleEndedIterator<Item = &'a T>,
+    timestamp: impl Fn(&T) -> u64,
+    from: u64,
+    to: u64,
+    resolution: u64,
+) -> Vec<Vec<&'a T>> {
+    let resolution = resolution.max(1);
+    let mut buckets: Vec<Vec<&T>> = Vec::new();
+    let mut bucket_index = None;
+    // Newest first, so that buckets are counted back from `to`
+    for item in items.rev() {
+        let timestamp = timestamp(item);
+        if timestamp > to {
+            continue;
+        }
+        if timestamp <= from {
+            break;
+        }
+        let index = (to - timestamp + resolution / 2) / resolution;
+        if bucket_index != Some(index) {
+            bucket_index = Some(index);
+            buckets.push(Vec::new());
+        }
+        buckets.last_mut().unwrap().push(item);
+    }
+    buckets.reverse();
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp: u64, usage: f32) -> SystemUtilization {
+        SystemUtilization {
+            timestamp,
+            mem: usage as u64,
+            cpus: vec![CpuCore {
+                usage,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn usages(rollups: &[Rollup]) -> Vec<(f32, f32, f32)> {
+        rollups
+            .iter()
+            .map(|rollup| {
+                (
+                    rollup.min.cpus[0].usage,
+                    rollup.avg.cpus[0].usage,
+                    rollup.max.cpus[0].usage,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn downsample_test() {
+        // A one second burst in otherwise idle samples
+        let samples: Vec<_> = (1..=10)
+            .map(|i| sample(i * 1000, if i == 4 { 100.0 } else { 0.0 }))
+            .collect();
+
+        let rollups = downsample(samples.iter(), 0, 10_000, 5000);
+        assert_eq!(
+            vec![(0.0, 0.0, 0.0), (0.0, 20.0, 100.0), (0.0, 0.0, 0.0)],
+            usages(&rollups)
+        );
+        // Centered on `to`, so the first bucket holds 2 samples and the last one 3
+        assert_eq!(1500, rollups[0].avg.timestamp);
+        assert_eq!(3000, rollups[1].min.timestamp);
+        assert_eq!(5000, rollups[1].avg.timestamp);
+        assert_eq!(7000, rollups[1].max.timestamp);
+        assert_eq!(9000, rollups[2].avg.timestamp);
+
+        // At full resolution every bucket holds a single sample
+        let rollups = downsample(samples.iter(), 5000, 10_000, 1000);
+        assert_eq!(5, rollups.len());
+        assert!(
+            usages(&rollups)
+                .iter()
+                .all(|&(min, avg, max)| min == avg && avg == max)
+        );
+
+        assert!(downsample(samples.iter(), 10_000, 20_000, 1000).is_empty());
+    }
+
+    #[test]
+    fn downsample_rollups_test() {
+        let rollups = [
+            Rollup {
+                min: sample(1000, 10.0),
+                avg: sample(5000, 20.0),
+                max: sample(9000, 90.0),
+                count: 3,
+            },
+            // Cut short, e.g. by quitting the app
+            Rollup {
+                min: sample(11_000, 0.0),
+                avg: sample(11_000, 40.0),
+                max: sample(11_000, 50.0),
+                count: 1,
+            },
+        ];
+
+        let merged = downsample_rollups(rollups.iter(), 0, 15_000, 40_000);
+        assert_eq!(vec![(0.0, 25.0, 90.0)], usages(&merged));
+        assert_eq!(0, merged[0].min.mem);
+        assert_eq!(90, merged[0].max.mem);
+        assert_eq!(6500, merged[0].avg.timestamp);
+        assert_eq!(4, merged[0].count);
+
+        let unchanged = downsample_rollups(rollups.iter(), 0, 15_000, 10_000);
+        assert_eq!(
+            vec![(10.0, 20.0, 90.0), (0.0, 40.0, 50.0)],
+            usages(&unchanged)
+        );
+    }
+
+    #[test]
+    fn gap_until_test() {
+        let rollup = |monotonic: u64, timestamp: u64| {
+            Rollup::from(SystemUtilization {
+                timestamp,
+                monotonic,
+                ..Default::default()
+            })
+        };
+        let before = rollup(50_000, 1_000_000);
+        assert!(!before.gap_until(&rollup(51_000, 1_001_000), 2000));
+        // Woke up from sleep
+        assert!(before.gap_until(&rollup(3_650_000, 4_600_000), 2000));
+        // The wall clock was set back, yet sampling went on
+        assert!(!before.gap_until(&rollup(51_000, 500_000), 2000));
+        // Rebooted
+        assert!(before.gap_until(&rollup(30_000, 1_100_000), 2000));
+    }
+}
@@ -1,67 +1,157 @@
+mod aggregate;
+mod columns;
+mod downsample;
+pub use aggregate::*;
+pub use columns::*;
+pub use downsample::*;
+
 use std::time::Duration;
 
-use derive_more::{Add, Div, DivAssign, Sum};
+/// Tauri event carrying a new [`SystemUtilization`] sample
+pub const STATS_EVENT: &str = "stats";
+
+/// Time between two samples
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Periods of history that can be shown, in seconds
+pub const TIME_OPTIONS: [u64; 10] = [
+    60,
+    5 * 60,
+    30 * 60,
+    3 * 3600,
+    6 * 3600,
+    12 * 3600,
+    24 * 3600,
+    7 * 24 * 3600,
+    30 * 24 * 3600,
+    365 * 24 * 3600,
+];
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct SystemUtilization {
+    /// When the sample was taken, in milliseconds since the Unix epoch
+    pub timestamp: u64,
+    /// When the sample was taken, in milliseconds since boot. Unlike [`Self::timestamp`] it doesn't
+    /// jump when the wall clock is set, and it keeps counting while the system sleeps.
+    pub monotonic: u64,
     pub cpus: Vec<CpuCore>,
+    /// Usage over all cores in percent
+    pub cpu_usage: f32,
+    /// Breakdown over all cores
+    pub cpu_times: CpuTimes,
     pub mem: u64,
     pub mem_max: u64,
+    pub memory: Memory,
     pub disk: Disk,
+    pub disks: Vec<DiskDevice>,
     pub gpus: Vec<Gpu>,
     pub up_time: Duration,
     pub processes: u32,
     pub network: Network,
+    pub sensors: Vec<Sensor>,
 }
 
-impl std::ops::Add for SystemUtilization {
-    type Output = Self;
-
-    fn add(self, rhs: Self) -> Self::Output {
-        Self {
-            cpus: self
-                .cpus
-                .into_iter()
-                .zip(rhs.cpus)
-                .map(|(a, b)| a + b)
-                .collect(),
-            mem: self.mem + rhs.mem,
-            mem_max: self.mem_max + rhs.mem_max,
-            disk: self.disk + rhs.disk,
-            gpus: self
-                .gpus
-                .into_iter()
-                .zip(rhs.gpus)
-                .map(|(a, b)| a + b)
-                .collect(),
-            up_time: self.up_time + rhs.up_time,
-            processes: self.processes + rhs.processes,
-            network: self.network + rhs.network,
-        }
-    }
-}
-impl std::iter::Sum for SystemUtilization {
-    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
-        iter.fold(Self::default(), |a, b| a + b)
-    }
-}
-
-#[derive(Clone, Default, Serialize, Deserialize, Debug, Add, Div, DivAssign, Sum)]
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct CpuCore {
     pub usage: f32,
     pub freq: u64,
+    pub times: CpuTimes,
+}
+
+/// Percentage of time spent in each CPU state, Linux only
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct CpuTimes {
+    pub user: f32,
+    /// User time of low priority processes
+    pub nice: f32,
+    pub system: f32,
+    pub idle: f32,
+    /// Idle while waiting for I/O
+    pub iowait: f32,
+    pub irq: f32,
+    pub softirq: f32,
+    /// Time the hypervisor gave to other virtual machines
+    pub steal: f32,
+}
+
+impl CpuTimes {
+    /// Time spent in the kernel, including interrupt handling
+    pub fn kernel(&self) -> f32 {
+        self.system + self.irq + self.softirq
+    }
 }
 
-#[derive(Clone, Default, Serialize, Deserialize, Debug, Add, Div, DivAssign, Sum)]
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct Gpu {
+    /// Usage in percent
     pub usage: u32,
-    pub mem: u32,
-    pub max_mem: u64,
+    /// Time the memory controller was busy in percent, not how much memory is used
+    pub mem_busy: u32,
+    /// Video memory in bytes
+    pub vram_total: u64,
+    pub vram_used: u64,
+    /// Temperature in degrees Celsius
     pub temp: u32,
+    /// Power draw in watts
+    pub power: f32,
+    /// Power limit enforced by the driver in watts, 0 if unknown
+    pub power_limit: f32,
+    /// Graphics clock in MHz
+    pub graphics_clock: u32,
+    /// Memory clock in MHz
+    pub memory_clock: u32,
+    /// Fan speed in percent, `None` if the GPU has no fan (or doesn't report it)
+    pub fan: Option<u32>,
+    /// PCIe receive throughput in bytes per second
+    pub pcie_rx: u64,
+    /// PCIe transmit throughput in bytes per second
+    pub pcie_tx: u64,
+    /// Video encoder usage in percent
+    pub encoder: u32,
+    /// Video decoder usage in percent
+    pub decoder: u32,
+    /// Why clocks are currently held down, e.g. "power cap"
+    pub throttle_reasons: Vec<String>,
+}
+
+/// A process using a GPU, from the DRM fdinfo interface
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct GpuProcess {
+    pub pid: u32,
+    pub name: String,
+    /// PCI slot of the GPU, e.g. `0000:03:00.0`
+    pub pci_slot: String,
+    pub driver: String,
+    /// Usage of the busiest GPU engine in percent
+    pub usage: f32,
+    /// Video memory in bytes
+    pub vram: u64,
+}
+
+/// Memory composition, in bytes unless noted otherwise
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Memory {
+    /// Memory that can be handed out without swapping, including reclaimable caches
+    pub available: u64,
+    /// Completely unused memory
+    pub free: u64,
+    /// Page cache and reclaimable kernel slabs, Linux only
+    pub cached: u64,
+    /// Block device buffers, Linux only
+    pub buffers: u64,
+    /// Shared memory and tmpfs, Linux only
+    pub shared: u64,
+    pub swap_used: u64,
+    pub swap_total: u64,
+    /// Swapped in bytes per second, Linux only
+    pub swap_in: u64,
+    /// Swapped out bytes per second, Linux only
+    pub swap_out: u64,
 }
 
-#[derive(Clone, Default, Serialize, Deserialize, Debug, Add, Div, DivAssign, Sum)]
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct Disk {
     /// Read bytes
     pub read_bytes: u64,
@@ -69,12 +159,116 @@ pub struct Disk {
     pub writen_bytes: u64,
 }
 
-#[derive(Clone, Default, Serialize, Deserialize, Debug, Add, Div, DivAssign, Sum)]
+/// A single block device
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct DiskDevice {
+    pub name: String,
+    /// Read speed in bytes per second
+    pub read_bytes: u64,
+    /// Write speed in bytes per second
+    pub written_bytes: u64,
+    /// Completed read and write operations per second
+    pub iops: f32,
+    /// Percentage of time the device was busy
+    pub busy: f32,
+    /// Average time to complete an operation in milliseconds
+    pub latency: f32,
+}
+
+/// A temperature sensor, in degrees Celsius
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Sensor {
+    /// Driver exposing the sensor, e.g. `coretemp` or `nvme`. Empty if unknown.
+    pub chip: String,
+    /// Device the chip belongs to, e.g. `nvme0`, which tells apart chips of identical hardware.
+    /// Empty if unknown.
+    pub device: String,
+    pub label: String,
+    pub temp: f32,
+    /// Highest temperature the hardware is rated for, if reported
+    pub max: Option<f32>,
+    /// Temperature at which the hardware shuts down, if reported
+    pub critical: Option<f32>,
+}
+
+impl Sensor {
+    /// Whether this sensor measures the whole CPU package rather than a single core
+    pub fn is_cpu_package(&self) -> bool {
+        match self.chip.as_str() {
+            "coretemp" => self.label.starts_with("Package id"),
+            "k10temp" | "zenpower" => self.label == "Tctl" || self.label == "Tdie",
+            "cpu_thermal" => true,
+            // Without hwmon the chip is unknown and folded into the label
+            "" => {
+                self.label.contains("Package id")
+                    || self.label.ends_with("Tctl")
+                    || self.label.ends_with("Tdie")
+            }
+            _ => false,
+        }
+    }
+}
+
+impl SystemUtilization {
+    /// Temperature of the (first) CPU package, if there is a sensor for it
+    pub fn cpu_temp(&self) -> Option<f32> {
+        self.sensors
+            .iter()
+            .find(|sensor| sensor.is_cpu_package())
+            .map(|sensor| sensor.temp)
+    }
+}
+
+/// A mounted filesystem
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Filesystem {
+    pub mount_point: String,
+    pub device: String,
+    pub fs_type: String,
+    /// Total size in bytes
+    pub total: u64,
+    /// Used space in bytes
+    pub used: u64,
+    /// Space available to unprivileged users in bytes
+    pub available: u64,
+    /// `None` if the filesystem allocates inodes dynamically (e.g. btrfs) or on non-Unix platforms
+    pub inodes_total: Option<u64>,
+    pub inodes_used: Option<u64>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct Network {
+    /// Download speed in bytes per second, summed over included interfaces
+    pub down: u64,
+    /// Upload speed in bytes per second, summed over included interfaces
+    pub up: u64,
+    pub interfaces: Vec<NetworkInterface>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub mac: String,
+    /// Addresses with their prefix length, e.g. `192.168.1.2/24`
+    pub ips: Vec<String>,
+    /// Whether the interface counts towards [`Network`]'s totals
+    pub included: bool,
     /// Download speed in bytes per second
     pub down: u64,
     /// Upload speed in bytes per second
     pub up: u64,
+    /// Received packets per second
+    pub packets_down: u64,
+    /// Transmitted packets per second
+    pub packets_up: u64,
+    /// Receive errors since the interface came up
+    pub errors_down: u64,
+    /// Transmit errors since the interface came up
+    pub errors_up: u64,
+    /// Dropped incoming packets since the interface came up, Linux only
+    pub drops_down: u64,
+    /// Dropped outgoing packets since the interface came up, Linux only
+    pub drops_up: u64,
 }
 
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
@@ -88,4 +282,252 @@ pub struct SystemInfo {
     pub max_mem: u64,
     pub gpu_count: u32,
     pub gpu_names: Vec<String>,
+    /// PCI slot of each GPU, empty if unknown
+    pub gpu_pci_slots: Vec<String>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub parent: Option<u32>,
+    pub name: String,
+    pub user: String,
+    pub cmd: String,
+    /// CPU usage in percent of the whole CPU (all cores)
+    pub cpu_usage: f32,
+    /// Resident set size in bytes
+    pub mem: u64,
+    /// Disk read speed in bytes per second
+    pub disk_read: u64,
+    /// Disk write speed in bytes per second
+    pub disk_write: u64,
+    pub state: String,
+}
+
+/// A process with its children and the usage of its whole subtree (itself included)
+#[derive(Clone, Debug)]
+pub struct ProcessNode {
+    pub process: ProcessInfo,
+    pub children: Vec<ProcessNode>,
+    pub subtree_cpu_usage: f32,
+    pub subtree_mem: u64,
+    pub subtree_disk_read: u64,
+    pub subtree_disk_write: u64,
+}
+
+impl ProcessNode {
+    pub fn new(process: ProcessInfo, children: Vec<ProcessNode>) -> Self {
+        let mut node = Self {
+            subtree_cpu_usage: process.cpu_usage,
+            subtree_mem: process.mem,
+            subtree_disk_read: process.disk_read,
+            subtree_disk_write: process.disk_write,
+            process,
+            children: Vec::new(),
+        };
+        for child in &children {
+            node.subtree_cpu_usage += child.subtree_cpu_usage;
+            node.subtree_mem += child.subtree_mem;
+            node.subtree_disk_read += child.subtree_disk_read;
+            node.subtree_disk_write += child.subtree_disk_write;
+        }
+        node.children = children;
+        node
+    }
+}
+
+/// Human readable size in binary units, e.g. `20.4 MiB`
+pub fn print_bytes(value: u64) -> String {
+    let mut value = value as f32;
+    let suffixes = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let base = 1024.0;
+    let mut pow = 0;
+    while (value >= base) && pow < suffixes.len() - 1 {
+        value /= base;
+        pow += 1;
+    }
+    format!("{:.1} {}", value, suffixes.get(pow).unwrap())
+}
+
+/// Rounded down to a single unit, e.g. `3 min`
+pub fn print_secs(value: u64) -> String {
+    const DAY: u64 = 24 * 3600;
+    const YEAR: u64 = 365 * DAY;
+    if value >= YEAR {
+        return format!("{} y", value / YEAR);
+    }
+    // A day reads better in hours
+    if value > DAY {
+        return format!("{} d", value / DAY);
+    }
+
+    let mut value = value;
+    let suffixes = ["s", "min", "h"];
+    let base = 60;
+    let mut pow = 0;
+    while (value >= base) && pow < suffixes.len() - 1 {
+        value /= base;
+        pow += 1;
+    }
+    format!("{} {}", value, suffixes.get(pow).unwrap())
+}
+
+/// Builds a forest out of a flat process list.
+///
+/// Processes whose parent isn't in the list become roots.
+pub fn process_tree(processes: Vec<ProcessInfo>) -> Vec<ProcessNode> {
+    use std::collections::{HashMap, HashSet};
+
+    let pids: HashSet<u32> = processes.iter().map(|proc| proc.pid).collect();
+    let mut children: HashMap<u32, Vec<ProcessInfo>> = HashMap::new();
+    let mut roots = Vec::new();
+    for proc in processes {
+        match proc.parent {
+            Some(parent) if parent != proc.pid && pids.contains(&parent) => {
+                children.entry(parent).or_default().push(proc)
+            }
+            _ => roots.push(proc),
+        }
+    }
+
+    fn build(proc: ProcessInfo, children: &mut HashMap<u32, Vec<ProcessInfo>>) -> ProcessNode {
+        let own_children = children
+            .remove(&proc.pid)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| build(child, children))
+            .collect();
+        ProcessNode::new(proc, own_children)
+    }
+
+    let mut forest: Vec<_> = roots
+        .into_iter()
+        .map(|root| build(root, &mut children))
+        .collect();
+    // Whatever is left forms parent cycles (e.g. due to pid reuse), unreachable from any root
+    while let Some(&pid) = children.keys().next() {
+        for proc in children.remove(&pid).unwrap_or_default() {
+            forest.push(build(proc, &mut children));
+        }
+    }
+    forest
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum ProcessSignal {
+    /// SIGTERM
+    Terminate,
+    /// SIGKILL
+    Kill,
+    /// SIGSTOP
+    Suspend,
+    /// SIGCONT
+    Resume,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum ProcessActionError {
+    /// The process doesn't exist (anymore)
+    NotFound,
+    PermissionDenied,
+    /// The signal isn't supported on this platform
+    Unsupported,
+}
+
+impl std::fmt::Display for ProcessActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ProcessActionError::NotFound => "Process not found",
+            ProcessActionError::PermissionDenied => "Permission denied",
+            ProcessActionError::Unsupported => "Not supported on this platform",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_bytes_test() {
+        let test_cases = [
+            (0, "0.0 B"),
+            (1023, "1023.0 B"),
+            (1024, "1.0 KiB"),
+            (21_372_137, "20.4 MiB"),
+            (2_137_213_721_372_137, "1943.8 TiB"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(expected, print_bytes(input));
+        }
+    }
+
+    #[test]
+    fn print_time_test() {
+        let test_cases = [
+            (0, "0 s"),
+            (59, "59 s"),
+            (60, "1 min"),
+            (3 * 60, "3 min"),
+            (3 * 60 + 1, "3 min"),
+            (60, "1 min"),
+            (5 * 60, "5 min"),
+            (30 * 60, "30 min"),
+            (3 * 60 * 60, "3 h"),
+            (6 * 60 * 60, "6 h"),
+            (12 * 60 * 60, "12 h"),
+            (24 * 60 * 60, "24 h"),
+            (7 * 24 * 60 * 60, "7 d"),
+            (30 * 24 * 60 * 60, "30 d"),
+            (365 * 24 * 60 * 60, "1 y"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(expected, print_secs(input));
+        }
+    }
+
+    fn process(pid: u32, parent: Option<u32>, cpu_usage: f32, mem: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            parent,
+            cpu_usage,
+            mem,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn process_tree_subtree_totals() {
+        let processes = vec![
+            process(1, None, 1.0, 10),
+            process(2, Some(1), 2.0, 20),
+            process(3, Some(2), 3.0, 30),
+            process(4, Some(2), 4.0, 40),
+            // Parent not in the list
+            process(5, Some(100), 5.0, 50),
+        ];
+        let mut forest = process_tree(processes);
+        forest.sort_by_key(|node| node.process.pid);
+
+        assert_eq!(2, forest.len());
+        let init = &forest[0];
+        assert_eq!(10.0, init.subtree_cpu_usage);
+        assert_eq!(100, init.subtree_mem);
+        let child = &init.children[0];
+        assert_eq!(2, child.process.pid);
+        assert_eq!(2, child.children.len());
+        assert_eq!(9.0, child.subtree_cpu_usage);
+        assert_eq!(90, child.subtree_mem);
+        assert_eq!(5, forest[1].process.pid);
+        assert_eq!(50, forest[1].subtree_mem);
+    }
+
+    #[test]
+    fn process_tree_parent_cycle() {
+        let processes = vec![process(1, Some(2), 1.0, 1), process(2, Some(1), 1.0, 1)];
+        let forest = process_tree(processes);
+
+        assert_eq!(1, forest.len());
+        assert_eq!(2, forest[0].subtree_mem);
+    }
 }
@@ -0,0 +1,189 @@
+use shared::DiskDevice;
+
+use std::{collections::HashMap, fs, path::PathBuf, time::Instant};
+
+/// `/proc/diskstats` always counts in 512 byte sectors, regardless of the device's sector size
+const SECTOR_SIZE: u64 = 512;
+
+/// Cumulative counters from a single line of `/proc/diskstats`
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiskStatsEntry {
+    pub name: String,
+    pub reads: u64,
+    pub sectors_read: u64,
+    /// Time spent reading in milliseconds
+    pub read_ticks: u64,
+    pub writes: u64,
+    pub sectors_written: u64,
+    /// Time spent writing in milliseconds
+    pub write_ticks: u64,
+    /// Time spent doing I/O in milliseconds
+    pub io_ticks: u64,
+}
+
+/// See <https://www.kernel.org/doc/Documentation/ABI/testing/procfs-diskstats>
+pub fn parse_diskstats(text: &str) -> Vec<DiskStatsEntry> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(2);
+            let name = fields.next()?.to_owned();
+            let values = fields
+                .map(|field| field.parse::<u64>().ok())
+                .collect::<Option<Vec<_>>>()?;
+            if values.len() < 11 {
+                return None;
+            }
+            Some(DiskStatsEntry {
+                name,
+                reads: values[0],
+                sectors_read: values[2],
+                read_ticks: values[3],
+                writes: values[4],
+                sectors_written: values[6],
+                write_ticks: values[7],
+                io_ticks: values[9],
+            })
+        })
+        .collect()
+}
+
+pub struct DiskStatsCollector {
+    diskstats: PathBuf,
+    sys_block: PathBuf,
+    previous: HashMap<String, DiskStatsEntry>,
+    last_update: Instant,
+}
+
+impl DiskStatsCollector {
+    pub fn new() -> Self {
+        let mut collector = Self {
+            diskstats: PathBuf::from("/proc/diskstats"),
+            sys_block: PathBuf::from("/sys/block"),
+            previous: HashMap::new(),
+            last_update: Instant::now(),
+        };
+        collector.refresh();
+        collector
+    }
+
+    /// Whole block devices only, partitions and loop/ram devices are skipped.
+    fn is_device(&self, name: &str) -> bool {
+        !name.starts_with("loop") && !name.starts_with("ram") && self.sys_block.join(name).exists()
+    }
+
+    /// Devices built on top of other ones (LVM, dm-crypt, RAID), whose I/O is already counted.
+    pub fn is_stacked(&self, name: &str) -> bool {
+        fs::read_dir(self.sys_block.join(name).join("slaves"))
+            .is_ok_and(|mut slaves| slaves.next().is_some())
+    }
+
+    /// Returns rates since the previous call. Empty if `/proc/diskstats` isn't available.
+    pub fn refresh(&mut self) -> Vec<DiskDevice> {
+        let update_time = Instant::now();
+        let secs = update_time
+            .duration_since(self.last_update)
+            .as_secs_f32()
+            .max(f32::EPSILON);
+        self.last_update = update_time;
+
+        let entries = fs::read_to_string(&self.diskstats)
+            .map(|text| parse_diskstats(&text))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entry| self.is_device(&entry.name));
+
+        let mut current = HashMap::new();
+        let mut devices = Vec::new();
+        for entry in entries {
+            if let Some(prev) = self.previous.get(&entry.name) {
+                devices.push(device_rates(prev, &entry, secs));
+            }
+            current.insert(entry.name.clone(), entry);
+        }
+        self.previous = current;
+        devices
+    }
+}
+
+fn device_rates(prev: &DiskStatsEntry, curr: &DiskStatsEntry, secs: f32) -> DiskDevice {
+    // Counters wrap around or reset when a device is re-added
+    let delta = |prev: u64, curr: u64| curr.saturating_sub(prev);
+
+    let ops = delta(prev.reads, curr.reads) + delta(prev.writes, curr.writes);
+    let ticks = delta(prev.read_ticks, curr.read_ticks) + delta(prev.write_ticks, curr.write_ticks);
+    let sectors_read = delta(prev.sectors_read, curr.sectors_read);
+    let sectors_written = delta(prev.sectors_written, curr.sectors_written);
+    let io_ticks = delta(prev.io_ticks, curr.io_ticks);
+
+    DiskDevice {
+        name: curr.name.clone(),
+        read_bytes: (sectors_read as f32 * SECTOR_SIZE as f32 / secs) as u64,
+        written_bytes: (sectors_written as f32 * SECTOR_SIZE as f32 / secs) as u64,
+        iops: ops as f32 / secs,
+        busy: (io_ticks as f32 / (secs * 1000.0) * 100.0).min(100.0),
+        latency: if ops == 0 {
+            0.0
+        } else {
+            ticks as f32 / ops as f32
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISKSTATS: &str = "\
+ 259       0 nvme0n1 1000 10 80000 500 2000 20 160000 1500 0 1800 2000 0 0 0 0 50 100
+ 259       1 nvme0n1p1 100 0 800 50 0 0 0 0 0 40 50 0 0 0 0 0 0
+   7       0 loop0 12 0 24 1 0 0 0 0 0 4 1 0 0 0 0 0 0
+ 253       0 dm-0 900 0 70000 400 1900 0 150000 1400 0 1700 1800 0 0 0 0 0 0
+";
+
+    #[test]
+    fn parse_diskstats_test() {
+        let entries = parse_diskstats(DISKSTATS);
+        assert_eq!(4, entries.len());
+        assert_eq!(
+            DiskStatsEntry {
+                name: "nvme0n1".to_owned(),
+                reads: 1000,
+                sectors_read: 80000,
+                read_ticks: 500,
+                writes: 2000,
+                sectors_written: 160000,
+                write_ticks: 1500,
+                io_ticks: 1800,
+            },
+            entries[0]
+        );
+    }
+
+    #[test]
+    fn parse_diskstats_skips_malformed_lines() {
+        assert!(parse_diskstats("8 0 sda 1 2 3\n\n").is_empty());
+    }
+
+    #[test]
+    fn device_rates_test() {
+        let prev = parse_diskstats(DISKSTATS).remove(0);
+        let curr = DiskStatsEntry {
+            reads: prev.reads + 100,
+            sectors_read: prev.sectors_read + 2048,
+            read_ticks: prev.read_ticks + 50,
+            writes: prev.writes + 100,
+            sectors_written: prev.sectors_written + 4096,
+            write_ticks: prev.write_ticks + 150,
+            io_ticks: prev.io_ticks + 250,
+            ..prev.clone()
+        };
+        let device = device_rates(&prev, &curr, 0.5);
+
+        assert_eq!("nvme0n1", device.name);
+        assert_eq!(2 * 2048 * 512, device.read_bytes);
+        assert_eq!(2 * 4096 * 512, device.written_bytes);
+        assert_eq!(400.0, device.iops);
+        assert_eq!(50.0, device.busy);
+        assert_eq!(1.0, device.latency);
+    }
+}
@@ -0,0 +1,340 @@
+use shared::{Columns, Number, SystemUtilization};
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use parquet::{
+    basic::{Compression, ConvertedType, Repetition, Type as PhysicalType},
+    column::writer::ColumnWriter,
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::types::Type,
+};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// File formats the history can be exported to
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    /// JSON Lines, an object per sample
+    Jsonl,
+    Parquet,
+}
+
+impl Format {
+    /// Guesses the format from the extension of a file
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            "parquet" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Jsonl => "jsonl",
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+/// Samples flattened into [`Columns`], a row each
+pub(crate) struct Table {
+    /// Every column of any sample, in the order they first show up
+    columns: Vec<String>,
+    /// `None` where a sample doesn't have a column, e.g. a disk that wasn't plugged in yet
+    rows: Vec<Vec<Option<Number>>>,
+}
+
+impl Table {
+    pub(crate) fn new<'a>(samples: impl IntoIterator<Item = &'a SystemUtilization>) -> Self {
+        let mut columns = Vec::new();
+        let mut indices = HashMap::new();
+        let mut rows = Vec::new();
+        for sample in samples {
+            let mut row = vec![None; columns.len()];
+            sample.columns("", &mut |name, _kind, value| {
+                let index = *indices.entry(name).or_insert_with_key(|name| {
+                    columns.push(name.clone());
+                    columns.len() - 1
+                });
+                if index >= row.len() {
+                    row.resize(index + 1, None);
+                }
+                row[index] = Some(value);
+            });
+            rows.push(row);
+        }
+        for row in &mut rows {
+            row.resize(columns.len(), None);
+        }
+        Self { columns, rows }
+    }
+
+    /// Missing values are empty
+    fn write_csv(&self, writer: impl Write) -> io::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(&self.columns)?;
+        for row in &self.rows {
+            writer.write_record(
+                row.iter()
+                    .map(|value| value.map_or(String::new(), |value| value.to_string())),
+            )?;
+        }
+        writer.flush()
+    }
+
+    /// Missing values are left out of the objects
+    pub(crate) fn write_jsonl(&self, mut writer: impl Write) -> io::Result<()> {
+        for row in &self.rows {
+            let row = Row {
+                columns: &self.columns,
+                values: row,
+            };
+            serde_json::to_writer(&mut writer, &row)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+
+    /// Integers are unsigned 64-bit columns and the rest 32-bit floats, all in a single row group
+    fn write_parquet(&self, writer: impl Write + Send) -> parquet::errors::Result<()> {
+        let fields = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                // The values of a column come from the same field, so they're all of the same type
+                let builder = match self.rows.iter().find_map(|row| row[i]) {
+                    Some(Number::Float(_)) => {
+                        Type::primitive_type_builder(name, PhysicalType::FLOAT)
+                    }
+                    _ => Type::primitive_type_builder(name, PhysicalType::INT64)
+                        .with_converted_type(ConvertedType::UINT_64),
+                };
+                builder
+                    .with_repetition(Repetition::OPTIONAL)
+                    .build()
+                    .map(Arc::new)
+            })
+            .collect::<Result<_, _>>()?;
+        let schema = Type::group_type_builder("sample")
+            .with_fields(fields)
+            .build()?;
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = SerializedFileWriter::new(writer, Arc::new(schema), Arc::new(properties))?;
+
+        let mut row_group = writer.next_row_group()?;
+        let mut i = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            let values = self.rows.iter().map(|row| row[i]);
+            // 1 where there is a value
+            let levels: Vec<_> = values.clone().map(|value| value.is_some() as i16).collect();
+            match column.untyped() {
+                ColumnWriter::Int64ColumnWriter(writer) => {
+                    let values: Vec<_> = values
+                        .flatten()
+                        .map(|value| match value {
+                            Number::Integer(value) => value as i64,
+                            Number::Float(value) => value as i64,
+                        })
+                        .collect();
+                    writer.write_batch(&values, Some(&levels), None)?;
+                }
+                ColumnWriter::FloatColumnWriter(writer) => {
+                    let values: Vec<_> = values
+                        .flatten()
+                        .map(|value| match value {
+                            Number::Integer(value) => value as f32,
+                            Number::Float(value) => value,
+                        })
+                        .collect();
+                    writer.write_batch(&values, Some(&levels), None)?;
+                }
+                _ => unreachable!("only integer and float columns are written"),
+            }
+            column.close()?;
+            i += 1;
+        }
+        row_group.close()?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+/// A row of a [`Table`] as a JSON object
+struct Row<'a> {
+    columns: &'a [String],
+    values: &'a [Option<Number>],
+}
+
+impl Serialize for Row<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.columns
+                .iter()
+                .zip(self.values)
+                .filter_map(|(column, value)| Some((column, (*value)?))),
+        )
+    }
+}
+
+/// Writes the samples to `path`, a row each, with flattened column names like `cpu.3.usage`
+pub fn export<'a>(
+    samples: impl IntoIterator<Item = &'a SystemUtilization>,
+    format: Format,
+    path: &Path,
+) -> io::Result<()> {
+    let table = Table::new(samples);
+    let file = BufWriter::new(File::create(path)?);
+    match format {
+        Format::Csv => table.write_csv(file),
+        Format::Jsonl => table.write_jsonl(file),
+        Format::Parquet => table.write_parquet(file).map_err(io::Error::other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    use crate::test_util::{interface, sample};
+
+    /// An interface that only shows up in the second sample
+    fn samples() -> Vec<SystemUtilization> {
+        let mut second = sample(2000);
+        second
+            .network
+            .interfaces
+            .insert(0, interface("wlan0", 2000));
+        vec![sample(1000), second]
+    }
+
+    #[test]
+    fn table_test() {
+        let table = Table::new(&samples());
+        let column = |name: &str| {
+            let index = table.columns.iter().position(|column| column == name);
+            let index = index.unwrap_or_else(|| panic!("no column {name}"));
+            table.rows.iter().map(|row| row[index]).collect::<Vec<_>>()
+        };
+        assert_eq!("timestamp", table.columns[0]);
+        assert_eq!(
+            vec![Some(Number::Integer(1000)), Some(Number::Integer(2000))],
+            column("network.interface.eth0.down")
+        );
+        assert_eq!(
+            vec![None, Some(Number::Integer(2000))],
+            column("network.interface.wlan0.down")
+        );
+        assert!(
+            table
+                .rows
+                .iter()
+                .all(|row| row.len() == table.columns.len())
+        );
+    }
+
+    #[test]
+    fn csv_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.csv");
+        export(&samples(), Format::Csv, &path).unwrap();
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let headers = reader.headers().unwrap().clone();
+        let rows: Vec<_> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(2, rows.len());
+        let wlan0 = headers
+            .iter()
+            .position(|header| header == "network.interface.wlan0.down")
+            .unwrap();
+        assert_eq!("", &rows[0][wlan0]);
+        assert_eq!("2000", &rows[1][wlan0]);
+        assert_eq!(
+            "3221225472",
+            &rows[1][headers.iter().position(|h| h == "mem").unwrap()]
+        );
+    }
+
+    #[test]
+    fn jsonl_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        export(&samples(), Format::Jsonl, &path).unwrap();
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(2, lines.len());
+        assert_eq!(1000, lines[0]["timestamp"]);
+        assert_eq!(0.0, lines[0]["cpu_times.user"]);
+        assert!(lines[0].get("network.interface.wlan0.down").is_none());
+        assert_eq!(2000, lines[1]["network.interface.wlan0.down"]);
+    }
+
+    #[test]
+    fn parquet_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.parquet");
+        export(&samples(), Format::Parquet, &path).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(2, metadata.num_rows());
+        let fields = metadata.schema().get_fields();
+        let field = |name: &str| fields.iter().find(|field| field.name() == name).unwrap();
+        assert_eq!(PhysicalType::INT64, field("timestamp").get_physical_type());
+        assert_eq!(
+            ConvertedType::UINT_64,
+            field("timestamp").get_basic_info().converted_type()
+        );
+        assert_eq!(
+            PhysicalType::FLOAT,
+            field("cpu_times.user").get_physical_type()
+        );
+
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let wlan0 = |row: usize| {
+            rows[row]
+                .get_column_iter()
+                .find(|(name, _)| *name == "network.interface.wlan0.down")
+                .map(|(_, value)| value.to_string())
+                .unwrap()
+        };
+        assert_eq!("null", wlan0(0));
+        assert_eq!("2000", wlan0(1));
+    }
+
+    #[test]
+    fn format_test() {
+        assert_eq!(Some(Format::Csv), Format::from_path(Path::new("a/b.csv")));
+        assert_eq!(
+            Some(Format::Jsonl),
+            Format::from_path(Path::new("b.ndjson"))
+        );
+        assert_eq!(
+            Some(Format::Parquet),
+            Format::from_path(Path::new("b.parquet"))
+        );
+        assert_eq!(None, Format::from_path(Path::new("b")));
+    }
+}
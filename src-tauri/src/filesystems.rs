@@ -0,0 +1,130 @@
+use shared::Filesystem;
+
+use std::path::Path;
+
+/// Pseudo and in-memory filesystems, which don't take up any storage
+pub const DEFAULT_EXCLUDED_TYPES: &[&str] = &[
+    "tmpfs",
+    "devtmpfs",
+    "ramfs",
+    "overlay",
+    "squashfs",
+    "efivarfs",
+    "fuse.portal",
+];
+pub const DEFAULT_EXCLUDED_MOUNT_POINTS: &[&str] = &["/snap", "/var/lib/docker"];
+
+#[derive(Clone, Debug)]
+pub struct FilesystemFilter {
+    /// Filesystem types matched exactly
+    pub excluded_types: Vec<String>,
+    /// Mount point prefixes, matched on whole path components
+    pub excluded_mount_points: Vec<String>,
+}
+
+impl Default for FilesystemFilter {
+    fn default() -> Self {
+        Self {
+            excluded_types: DEFAULT_EXCLUDED_TYPES
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            excluded_mount_points: DEFAULT_EXCLUDED_MOUNT_POINTS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl FilesystemFilter {
+    pub fn is_excluded(&self, fs_type: &str, mount_point: &Path) -> bool {
+        self.excluded_types
+            .iter()
+            .any(|excluded| excluded == fs_type)
+            || self
+                .excluded_mount_points
+                .iter()
+                .any(|excluded| mount_point.starts_with(excluded))
+    }
+}
+
+pub fn filesystems(disks: &sysinfo::Disks, filter: &FilesystemFilter) -> Vec<Filesystem> {
+    disks
+        .list()
+        .iter()
+        .filter(|disk| {
+            !filter.is_excluded(&disk.file_system().to_string_lossy(), disk.mount_point())
+        })
+        .map(|disk| {
+            let total = disk.total_space();
+            let available = disk.available_space();
+            let stat = statvfs(disk.mount_point());
+            Filesystem {
+                mount_point: disk.mount_point().to_string_lossy().into_owned(),
+                device: disk.name().to_string_lossy().into_owned(),
+                fs_type: disk.file_system().to_string_lossy().into_owned(),
+                total,
+                // Unlike `total - available`, this doesn't count blocks reserved for root as used
+                used: stat
+                    .as_ref()
+                    .map_or(total.saturating_sub(available), |stat| stat.used),
+                available,
+                inodes_total: stat.as_ref().and_then(|stat| stat.inodes_total),
+                inodes_used: stat.as_ref().and_then(|stat| stat.inodes_used),
+            }
+        })
+        .collect()
+}
+
+struct StatVfs {
+    used: u64,
+    inodes_total: Option<u64>,
+    inodes_used: Option<u64>,
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)] // `statvfs` field types differ between platforms
+fn statvfs(path: &Path) -> Option<StatVfs> {
+    use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is a valid C string and `stat` is only read after a successful call
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+
+    let inodes_total = stat.f_files as u64;
+    let has_inodes = inodes_total > 0;
+    Some(StatVfs {
+        used: (stat.f_blocks as u64).saturating_sub(stat.f_bfree as u64) * stat.f_frsize as u64,
+        inodes_total: has_inodes.then_some(inodes_total),
+        inodes_used: has_inodes.then(|| inodes_total.saturating_sub(stat.f_ffree as u64)),
+    })
+}
+
+#[cfg(not(unix))]
+fn statvfs(_path: &Path) -> Option<StatVfs> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filesystem_filter_test() {
+        let filter = FilesystemFilter::default();
+        assert!(filter.is_excluded("tmpfs", Path::new("/tmp")));
+        assert!(filter.is_excluded("overlay", Path::new("/")));
+        assert!(filter.is_excluded("squashfs", Path::new("/snap/core/1")));
+        assert!(filter.is_excluded("ext4", Path::new("/snap/core/1")));
+        assert!(!filter.is_excluded("ext4", Path::new("/snapshots")));
+        assert!(!filter.is_excluded("ext4", Path::new("/")));
+        assert!(!filter.is_excluded("btrfs", Path::new("/home")));
+    }
+}
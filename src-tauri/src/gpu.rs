@@ -0,0 +1,176 @@
+mod amdgpu;
+mod fdinfo;
+mod intel;
+mod mock;
+mod nvml;
+
+pub use amdgpu::AmdGpuBackend;
+pub use fdinfo::GpuProcessCollector;
+pub use intel::IntelGpuBackend;
+pub use mock::MockGpuBackend;
+pub use nvml::NvmlBackend;
+
+use shared::Gpu;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// A source of GPU metrics, usually one per vendor driver.
+pub trait GpuBackend: Send {
+    /// One name per GPU, fixed for the lifetime of the backend
+    fn names(&self) -> Vec<String>;
+    /// PCI slot of each GPU in the `0000:03:00.0` form used by DRM, empty if unknown
+    fn pci_slots(&self) -> Vec<String>;
+    /// Current utilization, in the same order as [`GpuBackend::names`]
+    fn utilization(&mut self) -> Vec<Gpu>;
+}
+
+/// All GPUs of the system, possibly from different vendors
+pub struct GpuCollector {
+    backends: Vec<Box<dyn GpuBackend>>,
+}
+
+impl GpuCollector {
+    /// Uses `mock_gpus` fake GPUs instead of the real ones if it isn't 0.
+    pub fn detect(mock_gpus: usize) -> Self {
+        if mock_gpus > 0 {
+            return Self::with_backends(vec![Box::new(MockGpuBackend::new(mock_gpus))]);
+        }
+
+        let mut backends: Vec<Box<dyn GpuBackend>> = Vec::new();
+        if let Some(nvml) = NvmlBackend::new() {
+            backends.push(Box::new(nvml));
+        }
+        let drm = Path::new("/sys/class/drm");
+        if let Some(amdgpu) = AmdGpuBackend::new(drm) {
+            backends.push(Box::new(amdgpu));
+        }
+        if let Some(intel) = IntelGpuBackend::new(drm) {
+            backends.push(Box::new(intel));
+        }
+        Self::with_backends(backends)
+    }
+
+    pub fn with_backends(backends: Vec<Box<dyn GpuBackend>>) -> Self {
+        Self { backends }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.backends
+            .iter()
+            .flat_map(|backend| backend.names())
+            .collect()
+    }
+
+    pub fn pci_slots(&self) -> Vec<String> {
+        self.backends
+            .iter()
+            .flat_map(|backend| backend.pci_slots())
+            .collect()
+    }
+
+    pub fn utilization(&mut self) -> Vec<Gpu> {
+        self.backends
+            .iter_mut()
+            .flat_map(|backend| backend.utilization())
+            .collect()
+    }
+}
+
+/// `cardN` directories under `drm` whose kernel driver is `driver`
+fn drm_cards(drm: &Path, driver: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(drm) else {
+        return Vec::new();
+    };
+    let mut cards: Vec<_> = entries
+        .flatten()
+        .filter(|entry| {
+            // Skips connectors such as `card0-DP-1` and render nodes
+            entry
+                .file_name()
+                .to_string_lossy()
+                .strip_prefix("card")
+                .is_some_and(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+        })
+        .map(|entry| entry.path())
+        .filter(|card| {
+            fs::read_to_string(card.join("device/uevent")).is_ok_and(|uevent| {
+                uevent
+                    .lines()
+                    .any(|line| line.strip_prefix("DRIVER=") == Some(driver))
+            })
+        })
+        .collect();
+    cards.sort_by_key(|path| (path.as_os_str().len(), path.clone()));
+    cards
+}
+
+fn read_value<T: FromStr>(path: impl AsRef<Path>) -> Option<T> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// The card's hwmon device, see <https://docs.kernel.org/hwmon/sysfs-interface.html>
+fn hwmon_dir(card: &Path) -> Option<PathBuf> {
+    let hwmon = fs::read_dir(card.join("device/hwmon"))
+        .ok()?
+        .flatten()
+        .next()?;
+    Some(hwmon.path())
+}
+
+/// First temperature of the card's hwmon device in degrees Celsius
+fn hwmon_temp(card: &Path) -> Option<u32> {
+    let millidegrees: u32 = read_value(hwmon_dir(card)?.join("temp1_input"))?;
+    Some(millidegrees / 1000)
+}
+
+/// The card's PCI slot, which its `device` symlink points to
+fn pci_slot(card: &Path) -> String {
+    fs::canonicalize(card.join("device"))
+        .ok()
+        .and_then(|device| Some(device.file_name()?.to_string_lossy().into_owned()))
+        .unwrap_or_default()
+}
+
+/// Product name of the card, if the driver reports one
+fn product_name(card: &Path) -> Option<String> {
+    fs::read_to_string(card.join("device/product_name"))
+        .ok()
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+fn fixture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/drm")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drm_cards_test() {
+        let cards = drm_cards(&fixture(), "amdgpu");
+        assert_eq!(vec![fixture().join("card0")], cards);
+        let cards = drm_cards(&fixture(), "i915");
+        assert_eq!(vec![fixture().join("card1")], cards);
+        assert!(drm_cards(&fixture(), "nouveau").is_empty());
+    }
+
+    #[test]
+    fn collector_concatenates_backends() {
+        let mut collector = GpuCollector::with_backends(vec![
+            Box::new(MockGpuBackend::new(2)),
+            Box::new(MockGpuBackend::new(1)),
+        ]);
+        assert_eq!(
+            vec!["Mock GPU 0", "Mock GPU 1", "Mock GPU 0"],
+            collector.names()
+        );
+        assert_eq!(3, collector.utilization().len());
+    }
+}
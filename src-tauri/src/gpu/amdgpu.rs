@@ -0,0 +1,102 @@
+use shared::Gpu;
+
+use std::path::{Path, PathBuf};
+
+use super::{GpuBackend, drm_cards, hwmon_dir, hwmon_temp, pci_slot, product_name, read_value};
+
+/// Reads the amdgpu sysfs interface, see <https://docs.kernel.org/gpu/amdgpu/driver-misc.html>
+pub struct AmdGpuBackend {
+    cards: Vec<PathBuf>,
+}
+
+impl AmdGpuBackend {
+    /// `None` if there are no amdgpu cards under `drm`, usually `/sys/class/drm`
+    pub fn new(drm: &Path) -> Option<Self> {
+        let cards = drm_cards(drm, "amdgpu");
+        (!cards.is_empty()).then_some(Self { cards })
+    }
+}
+
+impl GpuBackend for AmdGpuBackend {
+    fn names(&self) -> Vec<String> {
+        self.cards
+            .iter()
+            .map(|card| product_name(card).unwrap_or_else(|| "AMD Radeon".to_owned()))
+            .collect()
+    }
+
+    fn pci_slots(&self) -> Vec<String> {
+        self.cards.iter().map(|card| pci_slot(card)).collect()
+    }
+
+    fn utilization(&mut self) -> Vec<Gpu> {
+        self.cards
+            .iter()
+            .map(|card| {
+                let device = card.join("device");
+                // Power, clocks and the fan are only read if the card has a hwmon device
+                let hwmon = hwmon_dir(card);
+                let hwmon_value = |file| read_value::<u64>(hwmon.as_ref()?.join(file));
+                let microwatts = |file| hwmon_value(file).map_or(0.0, |uw| uw as f32 / 1e6);
+                let megahertz = |file| hwmon_value(file).map_or(0, |hz| (hz / 1_000_000) as u32);
+                Gpu {
+                    usage: read_value(device.join("gpu_busy_percent")).unwrap_or(0),
+                    mem_busy: read_value(device.join("mem_busy_percent")).unwrap_or(0),
+                    vram_total: read_value(device.join("mem_info_vram_total")).unwrap_or(0),
+                    vram_used: read_value(device.join("mem_info_vram_used")).unwrap_or(0),
+                    temp: hwmon_temp(card).unwrap_or(0),
+                    power: microwatts("power1_average"),
+                    power_limit: microwatts("power1_cap"),
+                    graphics_clock: megahertz("freq1_input"),
+                    memory_clock: megahertz("freq2_input"),
+                    // PWM duty cycle between 0 and 255
+                    fan: hwmon_value("pwm1").map(|pwm| (pwm * 100 / 255) as u32),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::gpu::fixture;
+
+    #[test]
+    fn amdgpu_utilization_test() {
+        let mut backend = AmdGpuBackend::new(&fixture()).unwrap();
+        assert_eq!(vec!["AMD Radeon RX 7800 XT"], backend.names());
+
+        let gpus = backend.utilization();
+        assert_eq!(1, gpus.len());
+        assert_eq!(37, gpus[0].usage);
+        assert_eq!(12, gpus[0].mem_busy);
+        assert_eq!(17163091968, gpus[0].vram_total);
+        assert_eq!(1073741824, gpus[0].vram_used);
+        assert_eq!(52, gpus[0].temp);
+        assert_eq!(45.0, gpus[0].power);
+        assert_eq!(263.0, gpus[0].power_limit);
+        assert_eq!(2124, gpus[0].graphics_clock);
+        assert_eq!(1249, gpus[0].memory_clock);
+        assert_eq!(Some(40), gpus[0].fan);
+    }
+
+    #[test]
+    fn amdgpu_without_hwmon_test() {
+        let drm = tempfile::tempdir().unwrap();
+        let device = drm.path().join("card0/device");
+        fs::create_dir_all(&device).unwrap();
+        fs::write(device.join("uevent"), "DRIVER=amdgpu\n").unwrap();
+        fs::write(device.join("gpu_busy_percent"), "37\n").unwrap();
+
+        let gpus = AmdGpuBackend::new(drm.path()).unwrap().utilization();
+        assert_eq!(37, gpus[0].usage);
+        assert_eq!(0.0, gpus[0].power);
+        assert_eq!(0, gpus[0].graphics_clock);
+        assert_eq!(None, gpus[0].fan);
+    }
+}
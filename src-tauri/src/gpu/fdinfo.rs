@@ -0,0 +1,300 @@
+use shared::GpuProcess;
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// A DRM client as reported in `/proc/<pid>/fdinfo/<fd>`.
+///
+/// See <https://docs.kernel.org/gpu/drm-usage-stats.html>
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DrmClient {
+    pub driver: String,
+    /// PCI slot of the GPU, e.g. `0000:03:00.0`
+    pub pdev: String,
+    pub client_id: u64,
+    /// Busy time per engine in nanoseconds
+    pub engine_time: HashMap<String, u64>,
+    /// Number of engines of each kind, 1 if not reported
+    pub engine_capacity: HashMap<String, u64>,
+    /// Busy and total GPU cycles per engine, reported by xe instead of busy time
+    pub engine_cycles: HashMap<String, (u64, u64)>,
+    /// Device local memory in bytes
+    pub vram: u64,
+}
+
+/// Parses sizes like `1024 KiB`
+fn parse_bytes(value: &str) -> Option<u64> {
+    let mut parts = value.split_whitespace();
+    let amount: u64 = parts.next()?.parse().ok()?;
+    let multiplier = match parts.next() {
+        None => 1,
+        Some("KiB") => 1 << 10,
+        Some("MiB") => 1 << 20,
+        Some("GiB") => 1 << 30,
+        Some(_) => return None,
+    };
+    Some(amount * multiplier)
+}
+
+/// Memory regions that live on the GPU rather than in system memory
+fn is_local_region(region: &str) -> bool {
+    region.starts_with("vram") || region.starts_with("local")
+}
+
+/// `None` if the file doesn't belong to a DRM client
+pub fn parse_fdinfo(text: &str) -> Option<DrmClient> {
+    let mut client = DrmClient::default();
+    let mut cycles = HashMap::new();
+    let mut total_cycles = HashMap::new();
+    // Newer kernels report `drm-resident-*` next to the deprecated `drm-memory-*`
+    let mut resident = None::<u64>;
+    let mut memory = None::<u64>;
+
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        let Some(key) = key.strip_prefix("drm-") else {
+            continue;
+        };
+
+        if key == "driver" {
+            client.driver = value.to_owned();
+        } else if key == "pdev" {
+            client.pdev = value.to_owned();
+        } else if key == "client-id" {
+            client.client_id = value.parse().ok()?;
+        } else if let Some(engine) = key.strip_prefix("engine-capacity-") {
+            client
+                .engine_capacity
+                .insert(engine.to_owned(), value.parse().ok()?);
+        } else if let Some(engine) = key.strip_prefix("engine-") {
+            let ns = value.strip_suffix("ns").unwrap_or(value).trim();
+            client
+                .engine_time
+                .insert(engine.to_owned(), ns.parse().ok()?);
+        } else if let Some(engine) = key.strip_prefix("total-cycles-") {
+            total_cycles.insert(engine.to_owned(), value.parse::<u64>().ok()?);
+        } else if let Some(engine) = key.strip_prefix("cycles-") {
+            cycles.insert(engine.to_owned(), value.parse::<u64>().ok()?);
+        } else if let Some(region) = key.strip_prefix("resident-") {
+            if is_local_region(region) {
+                *resident.get_or_insert(0) += parse_bytes(value)?;
+            }
+        } else if let Some(region) = key.strip_prefix("memory-")
+            && is_local_region(region)
+        {
+            *memory.get_or_insert(0) += parse_bytes(value)?;
+        }
+    }
+
+    client.engine_cycles = cycles
+        .into_iter()
+        .filter_map(|(engine, cycles)| {
+            let total = *total_cycles.get(&engine)?;
+            Some((engine, (cycles, total)))
+        })
+        .collect();
+    client.vram = resident.or(memory).unwrap_or(0);
+
+    (!client.driver.is_empty()).then_some(client)
+}
+
+/// Usage of the busiest engine between the two samples, in percent
+fn client_usage(prev: &DrmClient, curr: &DrmClient, elapsed_ns: u64) -> f32 {
+    let time_usage = curr.engine_time.iter().filter_map(|(engine, &time)| {
+        let prev_time = *prev.engine_time.get(engine)?;
+        let capacity = curr
+            .engine_capacity
+            .get(engine)
+            .copied()
+            .unwrap_or(1)
+            .max(1);
+        let busy = time.saturating_sub(prev_time) as f32 / capacity as f32;
+        Some(busy / elapsed_ns.max(1) as f32 * 100.0)
+    });
+    let cycle_usage = curr
+        .engine_cycles
+        .iter()
+        .filter_map(|(engine, &(cycles, total))| {
+            let &(prev_cycles, prev_total) = prev.engine_cycles.get(engine)?;
+            let total = total.saturating_sub(prev_total);
+            (total > 0).then(|| cycles.saturating_sub(prev_cycles) as f32 / total as f32 * 100.0)
+        });
+    time_usage.chain(cycle_usage).fold(0.0, f32::max).min(100.0)
+}
+
+/// DRM clients of a single process, skipping other file descriptors without reading their fdinfo
+fn process_clients(pid_dir: &Path) -> Vec<DrmClient> {
+    let Ok(fds) = fs::read_dir(pid_dir.join("fd")) else {
+        return Vec::new();
+    };
+    fds.flatten()
+        .filter(|fd| fs::read_link(fd.path()).is_ok_and(|target| target.starts_with("/dev/dri")))
+        .filter_map(|fd| {
+            let fdinfo = fs::read_to_string(pid_dir.join("fdinfo").join(fd.file_name())).ok()?;
+            parse_fdinfo(&fdinfo)
+        })
+        .collect()
+}
+
+/// Per-process GPU usage, computed between consecutive refreshes
+pub struct GpuProcessCollector {
+    proc: PathBuf,
+    /// Clients by PCI slot and client id
+    previous: HashMap<(String, u64), DrmClient>,
+    last_update: Instant,
+}
+
+impl GpuProcessCollector {
+    pub fn new() -> Self {
+        Self::with_proc_path("/proc")
+    }
+
+    pub fn with_proc_path(proc: impl Into<PathBuf>) -> Self {
+        Self {
+            proc: proc.into(),
+            previous: HashMap::new(),
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Processes using a GPU, busiest first. Usage is 0 for clients not seen in the previous call.
+    pub fn refresh(&mut self) -> Vec<GpuProcess> {
+        let update_time = Instant::now();
+        let elapsed_ns = update_time.duration_since(self.last_update).as_nanos() as u64;
+        self.last_update = update_time;
+
+        let Ok(entries) = fs::read_dir(&self.proc) else {
+            return Vec::new();
+        };
+        let mut pids: Vec<(u32, PathBuf)> = entries
+            .flatten()
+            .filter_map(|entry| Some((entry.file_name().to_str()?.parse().ok()?, entry.path())))
+            .collect();
+        // Shared clients are attributed to the oldest process, usually the parent
+        pids.sort_unstable();
+
+        let mut current = HashMap::new();
+        let mut processes = HashMap::<(u32, String), GpuProcess>::new();
+        for (pid, pid_dir) in pids {
+            for client in process_clients(&pid_dir) {
+                let key = (client.pdev.clone(), client.client_id);
+                // A client is listed once per file descriptor and by every process sharing it
+                if current.contains_key(&key) {
+                    continue;
+                }
+                let usage = self
+                    .previous
+                    .get(&key)
+                    .map_or(0.0, |prev| client_usage(prev, &client, elapsed_ns));
+                let process = processes
+                    .entry((pid, client.pdev.clone()))
+                    .or_insert_with(|| GpuProcess {
+                        pid,
+                        name: fs::read_to_string(pid_dir.join("comm"))
+                            .map(|comm| comm.trim().to_owned())
+                            .unwrap_or_default(),
+                        pci_slot: client.pdev.clone(),
+                        driver: client.driver.clone(),
+                        ..Default::default()
+                    });
+                process.usage = (process.usage + usage).min(100.0);
+                process.vram += client.vram;
+                current.insert(key, client);
+            }
+        }
+        self.previous = current;
+
+        let mut processes: Vec<_> = processes.into_values().collect();
+        processes.sort_by(|a, b| b.usage.total_cmp(&a.usage).then(b.vram.cmp(&a.vram)));
+        processes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(path: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(path)
+    }
+
+    fn read_fixture(path: &str) -> String {
+        fs::read_to_string(fixture(path)).unwrap()
+    }
+
+    #[test]
+    fn parse_fdinfo_amdgpu() {
+        let client = parse_fdinfo(&read_fixture("fdinfo/amdgpu")).unwrap();
+        assert_eq!("amdgpu", client.driver);
+        assert_eq!("0000:03:00.0", client.pdev);
+        assert_eq!(12, client.client_id);
+        assert_eq!(Some(&1_500_000_000), client.engine_time.get("gfx"));
+        assert_eq!(Some(&0), client.engine_time.get("compute"));
+        // `drm-resident-vram` takes precedence over `drm-memory-vram`
+        assert_eq!(262144 * 1024, client.vram);
+    }
+
+    #[test]
+    fn parse_fdinfo_i915() {
+        let client = parse_fdinfo(&read_fixture("fdinfo/i915")).unwrap();
+        assert_eq!("i915", client.driver);
+        assert_eq!(Some(&2), client.engine_capacity.get("video"));
+        assert_eq!(Some(&800_000_000), client.engine_time.get("render"));
+        assert_eq!(0, client.vram);
+    }
+
+    #[test]
+    fn parse_fdinfo_xe() {
+        let client = parse_fdinfo(&read_fixture("fdinfo/xe")).unwrap();
+        assert_eq!("xe", client.driver);
+        assert_eq!(Some(&(4000, 100_000)), client.engine_cycles.get("rcs"));
+        assert!(client.engine_time.is_empty());
+        assert_eq!(64 << 20, client.vram);
+    }
+
+    #[test]
+    fn parse_fdinfo_not_drm() {
+        assert_eq!(None, parse_fdinfo("pos:\t0\nflags:\t02\nmnt_id:\t15\n"));
+    }
+
+    #[test]
+    fn client_usage_test() {
+        let prev = parse_fdinfo(&read_fixture("fdinfo/i915")).unwrap();
+        let mut curr = prev.clone();
+        curr.engine_time
+            .insert("render".to_owned(), 800_000_000 + 250_000_000);
+        // Two video engines, both fully busy
+        curr.engine_time.insert("video".to_owned(), 3_000_000_000);
+        assert_eq!(100.0, client_usage(&prev, &curr, 1_000_000_000));
+        curr.engine_time.insert("video".to_owned(), 2_000_000_000);
+        assert_eq!(50.0, client_usage(&prev, &curr, 1_000_000_000));
+
+        let prev = parse_fdinfo(&read_fixture("fdinfo/xe")).unwrap();
+        let mut curr = prev.clone();
+        curr.engine_cycles
+            .insert("rcs".to_owned(), (4000 + 250, 100_000 + 1000));
+        assert_eq!(25.0, client_usage(&prev, &curr, 1_000_000_000));
+    }
+
+    #[test]
+    fn collector_test() {
+        let mut collector = GpuProcessCollector::with_proc_path(fixture("proc"));
+        let processes = collector.refresh();
+        // pid 200 shares its client with pid 100 and pid 300 doesn't use the GPU
+        assert_eq!(1, processes.len());
+        assert_eq!(100, processes[0].pid);
+        assert_eq!("glxgears", processes[0].name);
+        assert_eq!("0000:03:00.0", processes[0].pci_slot);
+        assert_eq!(262144 * 1024, processes[0].vram);
+        assert_eq!(0.0, processes[0].usage);
+    }
+}
@@ -0,0 +1,121 @@
+use shared::Gpu;
+
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use super::{GpuBackend, drm_cards, hwmon_temp, pci_slot, product_name, read_value};
+
+struct IntelCard {
+    path: PathBuf,
+    /// Idle residency counter in milliseconds
+    idle_residency: Option<PathBuf>,
+    previous: Option<(u64, Instant)>,
+}
+
+/// i915 and xe GPUs. Neither reports a busy percentage, so it's derived from
+/// the time the GPU spent in its idle (RC6) state.
+pub struct IntelGpuBackend {
+    cards: Vec<IntelCard>,
+}
+
+impl IntelGpuBackend {
+    /// `None` if there are no i915 or xe cards under `drm`, usually `/sys/class/drm`
+    pub fn new(drm: &Path) -> Option<Self> {
+        let cards: Vec<_> = drm_cards(drm, "i915")
+            .into_iter()
+            .chain(drm_cards(drm, "xe"))
+            .map(|path| {
+                let idle_residency = [
+                    "gt/gt0/rc6_residency_ms",
+                    "power/rc6_residency_ms",
+                    "device/tile0/gt0/gtidle/idle_residency_ms",
+                ]
+                .iter()
+                .map(|counter| path.join(counter))
+                .find(|counter| counter.exists());
+                IntelCard {
+                    path,
+                    idle_residency,
+                    previous: None,
+                }
+            })
+            .collect();
+        (!cards.is_empty()).then_some(Self { cards })
+    }
+}
+
+/// Share of `elapsed_ms` that wasn't spent idle. 0 if the idle counter went back, e.g. because it
+/// was reset or wrapped, as there's nothing to tell until the next reading.
+fn busy_percent(prev_idle_ms: u64, curr_idle_ms: u64, elapsed_ms: u64) -> u32 {
+    if elapsed_ms == 0 || curr_idle_ms < prev_idle_ms {
+        return 0;
+    }
+    let idle = (curr_idle_ms - prev_idle_ms).min(elapsed_ms);
+    (100 - idle * 100 / elapsed_ms) as u32
+}
+
+impl GpuBackend for IntelGpuBackend {
+    fn names(&self) -> Vec<String> {
+        self.cards
+            .iter()
+            .map(|card| product_name(&card.path).unwrap_or_else(|| "Intel Graphics".to_owned()))
+            .collect()
+    }
+
+    fn pci_slots(&self) -> Vec<String> {
+        self.cards.iter().map(|card| pci_slot(&card.path)).collect()
+    }
+
+    fn utilization(&mut self) -> Vec<Gpu> {
+        self.cards
+            .iter_mut()
+            .map(|card| {
+                let now = Instant::now();
+                let idle: Option<u64> = card.idle_residency.as_ref().and_then(read_value);
+                let usage = match (card.previous, idle) {
+                    (Some((prev_idle, prev_time)), Some(idle)) => {
+                        let elapsed = now.duration_since(prev_time).as_millis() as u64;
+                        busy_percent(prev_idle, idle, elapsed)
+                    }
+                    _ => 0,
+                };
+                card.previous = idle.map(|idle| (idle, now));
+                Gpu {
+                    usage,
+                    temp: hwmon_temp(&card.path).unwrap_or(0),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::gpu::fixture;
+
+    #[test]
+    fn busy_percent_test() {
+        assert_eq!(0, busy_percent(1000, 2000, 1000));
+        assert_eq!(75, busy_percent(1000, 1250, 1000));
+        assert_eq!(100, busy_percent(1000, 1000, 1000));
+        // Counter reset, the next reading is measured from the new value
+        assert_eq!(0, busy_percent(1000, 10, 1000));
+        assert_eq!(0, busy_percent(1000, 1500, 0));
+    }
+
+    #[test]
+    fn intel_detect_test() {
+        let mut backend = IntelGpuBackend::new(&fixture()).unwrap();
+        assert_eq!(vec!["Intel Graphics"], backend.names());
+        assert_eq!(
+            Some(fixture().join("card1/gt/gt0/rc6_residency_ms")),
+            backend.cards[0].idle_residency
+        );
+        assert_eq!(0, backend.utilization()[0].usage);
+    }
+}
@@ -0,0 +1,62 @@
+use shared::Gpu;
+
+use super::GpuBackend;
+
+/// Fake GPUs with changing, deterministic utilization
+pub struct MockGpuBackend {
+    count: usize,
+    tick: u32,
+}
+
+impl MockGpuBackend {
+    pub fn new(count: usize) -> Self {
+        Self { count, tick: 0 }
+    }
+}
+
+impl GpuBackend for MockGpuBackend {
+    fn names(&self) -> Vec<String> {
+        (0..self.count).map(|id| format!("Mock GPU {id}")).collect()
+    }
+
+    fn pci_slots(&self) -> Vec<String> {
+        vec![String::new(); self.count]
+    }
+
+    fn utilization(&mut self) -> Vec<Gpu> {
+        self.tick = self.tick.wrapping_add(1);
+        (0..self.count as u32)
+            .map(|id| {
+                let usage = (self.tick * 7 + id * 31) % 101;
+                Gpu {
+                    usage,
+                    mem_busy: usage / 2,
+                    vram_total: 8 << 30,
+                    vram_used: (8 << 30) / 100 * usage as u64,
+                    temp: 40 + usage / 3,
+                    power: 20.0 + usage as f32 * 2.0,
+                    power_limit: 220.0,
+                    graphics_clock: 300 + usage * 15,
+                    memory_clock: 5000,
+                    fan: Some(30 + usage / 2),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_utilization_test() {
+        let mut backend = MockGpuBackend::new(3);
+        let first = backend.utilization();
+        let second = backend.utilization();
+        assert_eq!(3, first.len());
+        assert!(first.iter().chain(&second).all(|gpu| gpu.usage <= 100));
+        assert_ne!(first[0].usage, second[0].usage);
+    }
+}
@@ -0,0 +1,302 @@
+use shared::Gpu;
+
+use nvml_wrapper::{
+    Device, Nvml,
+    bitmasks::device::ThrottleReasons,
+    enum_wrappers::device::{Clock, PcieUtilCounter, TemperatureSensor},
+};
+
+use super::GpuBackend;
+
+pub struct NvmlBackend {
+    nvml: Nvml,
+}
+
+impl NvmlBackend {
+    /// `None` if the NVIDIA driver isn't installed
+    pub fn new() -> Option<Self> {
+        Some(Self {
+            nvml: Nvml::init().ok()?,
+        })
+    }
+
+    fn device_count(&self) -> u32 {
+        self.nvml.device_count().unwrap_or(0)
+    }
+}
+
+/// NVML uses an 8 digit, upper case PCI domain, e.g. `00000000:01:00.0`
+fn drm_pci_slot(bus_id: &str) -> String {
+    let bus_id = bus_id.to_lowercase();
+    match bus_id.split_once(':') {
+        Some((domain, rest)) if domain.len() > 4 => {
+            format!("{}:{}", &domain[domain.len() - 4..], rest)
+        }
+        _ => bus_id,
+    }
+}
+
+/// The queries made on an NVML [`Device`], so that they can be mocked in tests.
+///
+/// Every query may fail, as not all GPUs support all of them.
+trait NvmlDevice {
+    /// GPU and memory controller usage in percent
+    fn utilization(&self) -> Option<(u32, u32)>;
+    /// Used and total memory in bytes
+    fn memory(&self) -> Option<(u64, u64)>;
+    fn temperature(&self) -> Option<u32>;
+    /// In milliwatts
+    fn power_usage(&self) -> Option<u32>;
+    /// In milliwatts
+    fn power_limit(&self) -> Option<u32>;
+    /// In MHz
+    fn clock(&self, clock: Clock) -> Option<u32>;
+    fn fan_speed(&self) -> Option<u32>;
+    /// In KB/s
+    fn pcie_throughput(&self, counter: PcieUtilCounter) -> Option<u32>;
+    fn encoder_utilization(&self) -> Option<u32>;
+    fn decoder_utilization(&self) -> Option<u32>;
+    fn throttle_reasons(&self) -> Option<ThrottleReasons>;
+}
+
+impl NvmlDevice for Device<'_> {
+    fn utilization(&self) -> Option<(u32, u32)> {
+        self.utilization_rates()
+            .ok()
+            .map(|util| (util.gpu, util.memory))
+    }
+
+    fn memory(&self) -> Option<(u64, u64)> {
+        self.memory_info()
+            .ok()
+            .map(|mem_info| (mem_info.used, mem_info.total))
+    }
+
+    fn temperature(&self) -> Option<u32> {
+        Device::temperature(self, TemperatureSensor::Gpu).ok()
+    }
+
+    fn power_usage(&self) -> Option<u32> {
+        Device::power_usage(self).ok()
+    }
+
+    fn power_limit(&self) -> Option<u32> {
+        self.enforced_power_limit().ok()
+    }
+
+    fn clock(&self, clock: Clock) -> Option<u32> {
+        self.clock_info(clock).ok()
+    }
+
+    fn fan_speed(&self) -> Option<u32> {
+        Device::fan_speed(self, 0).ok()
+    }
+
+    fn pcie_throughput(&self, counter: PcieUtilCounter) -> Option<u32> {
+        Device::pcie_throughput(self, counter).ok()
+    }
+
+    fn encoder_utilization(&self) -> Option<u32> {
+        Device::encoder_utilization(self)
+            .ok()
+            .map(|util| util.utilization)
+    }
+
+    fn decoder_utilization(&self) -> Option<u32> {
+        Device::decoder_utilization(self)
+            .ok()
+            .map(|util| util.utilization)
+    }
+
+    fn throttle_reasons(&self) -> Option<ThrottleReasons> {
+        self.current_throttle_reasons().ok()
+    }
+}
+
+/// Reasons worth showing to a user, idle GPUs and app clock settings are expected to be slow
+const THROTTLE_REASONS: [(ThrottleReasons, &str); 6] = [
+    (ThrottleReasons::SW_POWER_CAP, "power cap"),
+    (ThrottleReasons::HW_SLOWDOWN, "hardware slowdown"),
+    (ThrottleReasons::HW_POWER_BRAKE_SLOWDOWN, "power brake"),
+    (ThrottleReasons::SW_THERMAL_SLOWDOWN, "thermal"),
+    (ThrottleReasons::HW_THERMAL_SLOWDOWN, "hardware thermal"),
+    (ThrottleReasons::SYNC_BOOST, "sync boost"),
+];
+
+fn throttle_reason_names(reasons: ThrottleReasons) -> Vec<String> {
+    THROTTLE_REASONS
+        .iter()
+        .filter(|(reason, _)| reasons.contains(*reason))
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+fn gpu_metrics(device: &impl NvmlDevice) -> Gpu {
+    let (usage, mem_busy) = device.utilization().unwrap_or((0, 0));
+    let (vram_used, vram_total) = device.memory().unwrap_or((0, 0));
+    let milliwatts = |mw: Option<u32>| mw.map_or(0.0, |mw| mw as f32 / 1000.0);
+    let pcie = |counter| {
+        device
+            .pcie_throughput(counter)
+            .map_or(0, |kb_per_sec| kb_per_sec as u64 * 1024)
+    };
+
+    Gpu {
+        usage,
+        mem_busy,
+        vram_total,
+        vram_used,
+        temp: device.temperature().unwrap_or(0),
+        power: milliwatts(device.power_usage()),
+        power_limit: milliwatts(device.power_limit()),
+        graphics_clock: device.clock(Clock::Graphics).unwrap_or(0),
+        memory_clock: device.clock(Clock::Memory).unwrap_or(0),
+        fan: device.fan_speed(),
+        pcie_rx: pcie(PcieUtilCounter::Receive),
+        pcie_tx: pcie(PcieUtilCounter::Send),
+        encoder: device.encoder_utilization().unwrap_or(0),
+        decoder: device.decoder_utilization().unwrap_or(0),
+        throttle_reasons: device
+            .throttle_reasons()
+            .map_or(Vec::new(), throttle_reason_names),
+    }
+}
+
+impl GpuBackend for NvmlBackend {
+    fn names(&self) -> Vec<String> {
+        (0..self.device_count())
+            .map(|gpu_id| {
+                self.nvml
+                    .device_by_index(gpu_id)
+                    .map_or("".to_owned(), |device| {
+                        device.name().unwrap_or("".to_owned())
+                    })
+            })
+            .collect()
+    }
+
+    fn pci_slots(&self) -> Vec<String> {
+        (0..self.device_count())
+            .map(|gpu_id| {
+                self.nvml
+                    .device_by_index(gpu_id)
+                    .and_then(|device| device.pci_info())
+                    .map_or(String::new(), |pci| drm_pci_slot(&pci.bus_id))
+            })
+            .collect()
+    }
+
+    fn utilization(&mut self) -> Vec<Gpu> {
+        (0..self.device_count())
+            .map(|gpu_id| {
+                self.nvml
+                    .device_by_index(gpu_id)
+                    .map_or(Gpu::default(), |device| gpu_metrics(&device))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct MockDevice {
+        utilization: Option<(u32, u32)>,
+        memory: Option<(u64, u64)>,
+        power_usage: Option<u32>,
+        power_limit: Option<u32>,
+        fan_speed: Option<u32>,
+        pcie_rx: Option<u32>,
+        throttle_reasons: Option<ThrottleReasons>,
+    }
+
+    impl NvmlDevice for MockDevice {
+        fn utilization(&self) -> Option<(u32, u32)> {
+            self.utilization
+        }
+        fn memory(&self) -> Option<(u64, u64)> {
+            self.memory
+        }
+        fn temperature(&self) -> Option<u32> {
+            Some(65)
+        }
+        fn power_usage(&self) -> Option<u32> {
+            self.power_usage
+        }
+        fn power_limit(&self) -> Option<u32> {
+            self.power_limit
+        }
+        fn clock(&self, clock: Clock) -> Option<u32> {
+            match clock {
+                Clock::Graphics => Some(1800),
+                Clock::Memory => Some(7000),
+                _ => None,
+            }
+        }
+        fn fan_speed(&self) -> Option<u32> {
+            self.fan_speed
+        }
+        fn pcie_throughput(&self, counter: PcieUtilCounter) -> Option<u32> {
+            match counter {
+                PcieUtilCounter::Receive => self.pcie_rx,
+                PcieUtilCounter::Send => None,
+            }
+        }
+        fn encoder_utilization(&self) -> Option<u32> {
+            Some(10)
+        }
+        fn decoder_utilization(&self) -> Option<u32> {
+            None
+        }
+        fn throttle_reasons(&self) -> Option<ThrottleReasons> {
+            self.throttle_reasons
+        }
+    }
+
+    #[test]
+    fn gpu_metrics_test() {
+        let device = MockDevice {
+            utilization: Some((80, 30)),
+            memory: Some((2 << 30, 8 << 30)),
+            power_usage: Some(215_500),
+            power_limit: Some(320_000),
+            fan_speed: Some(55),
+            pcie_rx: Some(2048),
+            throttle_reasons: Some(ThrottleReasons::SW_POWER_CAP | ThrottleReasons::GPU_IDLE),
+        };
+        let gpu = gpu_metrics(&device);
+        assert_eq!(80, gpu.usage);
+        assert_eq!(30, gpu.mem_busy);
+        assert_eq!(2 << 30, gpu.vram_used);
+        assert_eq!(8 << 30, gpu.vram_total);
+        assert_eq!(215.5, gpu.power);
+        assert_eq!(320.0, gpu.power_limit);
+        assert_eq!(1800, gpu.graphics_clock);
+        assert_eq!(7000, gpu.memory_clock);
+        assert_eq!(Some(55), gpu.fan);
+        assert_eq!(2 << 20, gpu.pcie_rx);
+        assert_eq!(0, gpu.pcie_tx);
+        assert_eq!(10, gpu.encoder);
+        assert_eq!(0, gpu.decoder);
+        assert_eq!(vec!["power cap"], gpu.throttle_reasons);
+    }
+
+    #[test]
+    fn gpu_metrics_unsupported() {
+        let gpu = gpu_metrics(&MockDevice::default());
+        assert_eq!(0, gpu.usage);
+        assert_eq!(0, gpu.vram_total);
+        assert_eq!(0.0, gpu.power_limit);
+        assert_eq!(None, gpu.fan);
+        assert!(gpu.throttle_reasons.is_empty());
+    }
+
+    #[test]
+    fn drm_pci_slot_test() {
+        assert_eq!("0000:01:00.0", drm_pci_slot("00000000:01:00.0"));
+        assert_eq!("0000:0a:00.0", drm_pci_slot("00000000:0A:00.0"));
+        assert_eq!("0000:0a:00.0", drm_pci_slot("0000:0a:00.0"));
+    }
+}
@@ -0,0 +1,210 @@
+use shared::{Columns, Number, SystemUtilization, print_bytes};
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::{export::Table, monitor::SystemMonitor, sampler};
+
+/// How `sample` prints samples
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum Output {
+    /// JSON Lines, an object per sample with the columns of `--export`
+    Jsonl,
+    /// The columns of the first sample, later ones are empty where a sample doesn't have them
+    Csv,
+    /// A summary line per sample
+    Table,
+}
+
+/// Titles of the [`Output::Table`] columns, right aligned to their widths
+const TABLE_COLUMNS: [(&str, usize); 9] = [
+    ("CPU", 6),
+    ("MEM", 11),
+    ("SWAP", 11),
+    ("GPU", 5),
+    ("READ", 13),
+    ("WRITE", 13),
+    ("DOWN", 13),
+    ("UP", 13),
+    ("PROCS", 6),
+];
+
+fn print_rate(value: u64) -> String {
+    format!("{}/s", print_bytes(value))
+}
+
+/// The [`TABLE_COLUMNS`] of a sample
+fn table_row(sample: &SystemUtilization) -> [String; 9] {
+    let cpu =
+        sample.cpus.iter().map(|cpu| cpu.usage).sum::<f32>() / sample.cpus.len().max(1) as f32;
+    let gpu = if sample.gpus.is_empty() {
+        "-".to_owned()
+    } else {
+        let usage = sample.gpus.iter().map(|gpu| gpu.usage).sum::<u32>();
+        format!("{}%", usage / sample.gpus.len() as u32)
+    };
+    [
+        format!("{cpu:.1}%"),
+        print_bytes(sample.mem),
+        print_bytes(sample.memory.swap_used),
+        gpu,
+        print_rate(sample.disk.read_bytes),
+        print_rate(sample.disk.writen_bytes),
+        print_rate(sample.network.down),
+        print_rate(sample.network.up),
+        sample.processes.to_string(),
+    ]
+}
+
+fn write_table_row(mut writer: impl Write, cells: &[impl AsRef<str>]) -> io::Result<()> {
+    for (i, (cell, (_, width))) in cells.iter().zip(TABLE_COLUMNS).enumerate() {
+        let separator = if i == 0 { "" } else { " " };
+        write!(writer, "{separator}{:>width$}", cell.as_ref())?;
+    }
+    writeln!(writer)
+}
+
+/// Prints samples one at a time, as they're taken
+pub struct Printer<W: Write> {
+    writer: W,
+    output: Output,
+    /// Columns of the first sample, `None` until it's printed
+    header: Option<Vec<String>>,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(writer: W, output: Output) -> Self {
+        Self {
+            writer,
+            output,
+            header: None,
+        }
+    }
+
+    pub fn print(&mut self, sample: &SystemUtilization) -> io::Result<()> {
+        match self.output {
+            Output::Jsonl => Table::new([sample]).write_jsonl(&mut self.writer),
+            Output::Csv => self.print_csv(sample),
+            Output::Table => self.print_table(sample),
+        }
+    }
+
+    fn print_csv(&mut self, sample: &SystemUtilization) -> io::Result<()> {
+        let mut values = Vec::new();
+        sample.columns("", &mut |name, _kind, value| values.push((name, value)));
+        let mut writer = csv::Writer::from_writer(&mut self.writer);
+        if self.header.is_none() {
+            let header: Vec<_> = values.iter().map(|(name, _)| name.clone()).collect();
+            writer.write_record(&header)?;
+            self.header = Some(header);
+        }
+        let mut values: HashMap<String, Number> = values.into_iter().collect();
+        let header = self.header.as_ref().unwrap();
+        writer.write_record(header.iter().map(|name| {
+            values
+                .remove(name)
+                .map_or(String::new(), |value| value.to_string())
+        }))?;
+        writer.flush()
+    }
+
+    fn print_table(&mut self, sample: &SystemUtilization) -> io::Result<()> {
+        if self.header.is_none() {
+            let titles: Vec<_> = TABLE_COLUMNS
+                .iter()
+                .map(|(title, _)| title.to_string())
+                .collect();
+            write_table_row(&mut self.writer, &titles)?;
+            self.header = Some(titles);
+        }
+        write_table_row(&mut self.writer, &table_row(sample))?;
+        self.writer.flush()
+    }
+}
+
+/// Prints a sample of the system to stdout every `interval`, `count` times or until interrupted
+pub fn sample(
+    monitor: &mut SystemMonitor,
+    output: Output,
+    interval: Duration,
+    count: Option<u64>,
+) -> io::Result<()> {
+    if count == Some(0) {
+        return Ok(());
+    }
+    let mut printer = Printer::new(io::stdout().lock(), output);
+    let mut printed = 0;
+    let mut result = Ok(());
+    sampler::run(interval, || {
+        result = printer.print(&monitor.get_stats());
+        printed += 1;
+        result.is_ok() && count.is_none_or(|count| printed < count)
+    });
+    match result {
+        // Whatever reads the samples has had enough of them, e.g. `head`
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::{disk, sample};
+
+    fn print(output: Output) -> Vec<String> {
+        let mut printer = Printer::new(Vec::new(), output);
+        let mut second = sample(2000);
+        second.disks.insert(0, disk("sdb", 2000));
+        printer.print(&sample(1000)).unwrap();
+        printer.print(&second).unwrap();
+        String::from_utf8(printer.writer)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn jsonl_test() {
+        let lines: Vec<serde_json::Value> = print(Output::Jsonl)
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(2, lines.len());
+        assert_eq!(1000, lines[0]["timestamp"]);
+        assert_eq!(2000, lines[1]["disk.sdb.read_bytes"]);
+    }
+
+    #[test]
+    fn csv_test() {
+        let lines = print(Output::Csv);
+        assert_eq!(3, lines.len());
+        let header: Vec<_> = lines[0].split(',').collect();
+        let column = |line: &str, name: &str| {
+            let index = header.iter().position(|column| *column == name).unwrap();
+            line.split(',').nth(index).unwrap().to_owned()
+        };
+        assert_eq!("1000", column(&lines[1], "timestamp"));
+        assert_eq!("2000", column(&lines[2], "disk.sda.read_bytes"));
+        // Only the columns of the first sample
+        assert!(!header.contains(&"disk.sdb.read_bytes"));
+        assert_eq!(header.len(), lines[2].split(',').count());
+    }
+
+    #[test]
+    fn table_test() {
+        let lines = print(Output::Table);
+        assert_eq!(3, lines.len());
+        assert!(lines[0].trim_start().starts_with("CPU"));
+        let cells: Vec<_> = lines[1].split_whitespace().collect();
+        assert_eq!("37.5%", cells[0]);
+        assert_eq!(["3.0", "GiB"], cells[1..3]);
+        assert_eq!("42%", cells[5]);
+        assert_eq!(lines[0].len(), lines[1].len());
+    }
+}
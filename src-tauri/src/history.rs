@@ -0,0 +1,499 @@
+use shared::*;
+
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use crate::store::{Store, StoreConfig};
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long samples are kept at full resolution
+pub const RAW_LENGTH: Duration = HOUR;
+
+/// Bucket length and how long rollups are kept for each tier, finest first
+pub const TIERS: [(Duration, Duration); 3] = [
+    (Duration::from_secs(10), DAY),
+    (
+        Duration::from_secs(5 * 60),
+        Duration::from_secs(30 * 24 * 60 * 60),
+    ),
+    (HOUR, Duration::from_secs(365 * 24 * 60 * 60)),
+];
+
+/// The bucket of a [`Tier`] that is being filled, as running totals rather than its samples
+struct Pending {
+    bucket: u64,
+    min: SystemUtilization,
+    sum: SystemUtilization,
+    max: SystemUtilization,
+    /// How many samples every value is in, see [`Statistic::Count`]
+    counts: SystemUtilization,
+}
+
+impl Pending {
+    fn new(bucket: u64, sample: &SystemUtilization) -> Self {
+        Self {
+            bucket,
+            min: sample.clone(),
+            sum: sample.clone(),
+            max: sample.clone(),
+            counts: SystemUtilization::aggregate(&[sample], Statistic::Count),
+        }
+    }
+
+    fn add(&mut self, sample: &SystemUtilization) {
+        let add = |total: &SystemUtilization, statistic| {
+            SystemUtilization::aggregate(&[total, sample], statistic)
+        };
+        self.min = add(&self.min, Statistic::Min);
+        self.sum = add(&self.sum, Statistic::Sum);
+        self.max = add(&self.max, Statistic::Max);
+        let counts = SystemUtilization::aggregate(&[sample], Statistic::Count);
+        self.counts = SystemUtilization::aggregate(&[&self.counts, &counts], Statistic::Sum);
+    }
+
+    fn rollup(&self) -> Rollup {
+        Rollup {
+            min: self.min.clone(),
+            avg: SystemUtilization::aggregate(&[&self.sum, &self.counts], Statistic::MeanOfSum),
+            max: self.max.clone(),
+            // Every sample has a timestamp
+            count: self.counts.timestamp as u32,
+        }
+    }
+}
+
+/// Rollups of samples over buckets of the same length
+pub struct Tier {
+    /// Length of a bucket
+    pub resolution: Duration,
+    /// How long rollups are kept
+    pub length: Duration,
+    /// Completed buckets, oldest first. Those of a saved tier are only held while it's shown.
+    rollups: VecDeque<Rollup>,
+    pending: Option<Pending>,
+    /// Where completed buckets are saved, see [`History::save_to`]
+    store: Option<StoreConfig>,
+    /// Whether `rollups` holds the saved buckets
+    loaded: bool,
+}
+
+impl Tier {
+    pub fn new(resolution: Duration, length: Duration) -> Self {
+        Self {
+            resolution,
+            length,
+            rollups: VecDeque::new(),
+            pending: None,
+            store: None,
+            loaded: false,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        (self.length.as_secs() / self.resolution.as_secs()) as usize
+    }
+
+    /// Buckets are aligned to the Unix epoch
+    fn bucket(&self, sample: &SystemUtilization) -> u64 {
+        sample.timestamp / self.resolution.as_millis() as u64
+    }
+
+    /// Adds a completed rollup, dropping the oldest one once full. Left to the store if the saved
+    /// buckets aren't loaded.
+    fn push(&mut self, rollup: Rollup) {
+        if self.store.is_some() && !self.loaded {
+            return;
+        }
+        if self.rollups.len() == self.capacity() {
+            self.rollups.pop_front();
+        }
+        self.rollups.push_back(rollup);
+    }
+
+    /// Reads the saved buckets into memory, unless they already are
+    fn load(&mut self) {
+        let Some(config) = self.store.as_ref().filter(|_| !self.loaded) else {
+            return;
+        };
+        // Marked as loaded even if reading fails, so that it isn't retried on every query
+        self.loaded = true;
+        match Store::<Rollup>::read(config, 0) {
+            Ok(rollups) => {
+                let skip = rollups.len().saturating_sub(self.capacity());
+                self.rollups = rollups.into_iter().skip(skip).collect();
+            }
+            Err(err) => eprintln!("Failed to read the saved history: {err}"),
+        }
+    }
+
+    /// Frees the saved buckets, to be read again once shown
+    fn unload(&mut self) {
+        if self.store.is_some() {
+            self.rollups = VecDeque::new();
+            self.loaded = false;
+        }
+    }
+
+    /// Adds a sample to its bucket. Returns the rollup of the previous bucket if the sample starts
+    /// a new one, without the labels that aren't needed to tell its elements apart.
+    fn add(&mut self, sample: &SystemUtilization) -> Option<Rollup> {
+        let bucket = self.bucket(sample);
+        if let Some(pending) = &mut self.pending
+            && pending.bucket == bucket
+        {
+            pending.add(sample);
+            return None;
+        }
+        let mut rollup = self.pending.replace(Pending::new(bucket, sample))?.rollup();
+        rollup.clear_labels();
+        self.push(rollup.clone());
+        Some(rollup)
+    }
+}
+
+/// Recent samples at full resolution and rollups of older ones
+pub struct History {
+    /// Oldest first
+    samples: VecDeque<SystemUtilization>,
+    capacity: usize,
+    tiers: Vec<Tier>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::with_capacity((RAW_LENGTH.as_secs() / SAMPLE_INTERVAL.as_secs()) as usize)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            tiers: TIERS
+                .iter()
+                .map(|&(resolution, length)| Tier::new(resolution, length))
+                .collect(),
+        }
+    }
+
+    pub fn tiers(&self) -> &[Tier] {
+        &self.tiers
+    }
+
+    /// Adds a sample, dropping the oldest one once full.
+    /// Returns the rollups it completed, along with the index of their tier.
+    pub fn push(&mut self, sample: SystemUtilization) -> Vec<(usize, Rollup)> {
+        let rollups = self
+            .tiers
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, tier)| Some((i, tier.add(&sample)?)))
+            .collect();
+        self.push_sample(sample);
+        rollups
+    }
+
+    fn push_sample(&mut self, sample: SystemUtilization) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Loads saved samples and the rollups of every tier, all oldest first.
+    ///
+    /// Samples newer than the last rollup of a tier go into its next one, so that restarting
+    /// doesn't leave out the samples of the bucket that was being filled.
+    pub fn restore(&mut self, samples: Vec<SystemUtilization>, rollups: Vec<Vec<Rollup>>) {
+        for (tier, rollups) in self.tiers.iter_mut().zip(rollups) {
+            let last_bucket = rollups.last().map(|rollup| tier.bucket(&rollup.min));
+            for rollup in rollups {
+                tier.push(rollup);
+            }
+            for sample in &samples {
+                if Some(tier.bucket(sample)) > last_bucket {
+                    tier.add(sample);
+                }
+            }
+        }
+        for sample in samples {
+            self.push_sample(sample);
+        }
+    }
+
+    /// Keeps the completed buckets of every tier in `stores` instead of memory, reading them only
+    /// while the tier is shown. Every rollup [`Self::push`] returns has to be saved to the store of
+    /// its tier before the history is queried again.
+    pub fn save_to(&mut self, stores: Vec<StoreConfig>) {
+        for (tier, store) in self.tiers.iter_mut().zip(stores) {
+            tier.rollups = VecDeque::new();
+            tier.store = Some(store);
+            tier.loaded = false;
+        }
+    }
+
+    /// Keeps the completed buckets in memory again, e.g. once they can no longer be saved
+    pub fn stop_saving(&mut self) {
+        for tier in &mut self.tiers {
+            tier.load();
+            tier.store = None;
+        }
+    }
+
+    /// The index of the finest tier that covers the period, `None` if the samples at full
+    /// resolution do
+    fn tier(&self, from: u64, to: u64) -> Option<usize> {
+        let period = Duration::from_millis(to.saturating_sub(from));
+        if period <= RAW_LENGTH {
+            return None;
+        }
+        let index = self.tiers.iter().position(|tier| period <= tier.length);
+        Some(index.unwrap_or(self.tiers.len() - 1))
+    }
+
+    /// The finest resolution [`Self::range`] can roll the period up to, in milliseconds
+    fn finest_resolution(&self, from: u64, to: u64) -> u64 {
+        self.tier(from, to)
+            .map_or(SAMPLE_INTERVAL, |tier| self.tiers[tier].resolution)
+            .as_millis() as u64
+    }
+
+    /// Like [`Self::range`], at the finest resolution there is for the period
+    pub fn finest_range(&mut self, from: u64, to: u64) -> Vec<Rollup> {
+        self.range(from, to, self.finest_resolution(from, to))
+    }
+
+    /// Samples taken after `from` and up to `to`, rolled up into buckets `resolution` ms apart.
+    ///
+    /// Periods longer than [`RAW_LENGTH`] are read from the finest tier that covers them. Only the
+    /// saved buckets of that tier stay in memory.
+    pub fn range(&mut self, from: u64, to: u64, resolution: u64) -> Vec<Rollup> {
+        let index = self.tier(from, to);
+        for (i, tier) in self.tiers.iter_mut().enumerate() {
+            if Some(i) == index {
+                tier.load();
+            } else {
+                tier.unload();
+            }
+        }
+        let Some(tier) = index.map(|index| &self.tiers[index]) else {
+            return downsample(self.samples.iter(), from, to, resolution);
+        };
+        // The bucket being filled, so that the most recent samples show up
+        let pending = tier.pending.as_ref().map(Pending::rollup);
+        downsample_rollups(tier.rollups.iter().chain(&pending), from, to, resolution)
+    }
+}
+
+pub struct HistoryState(Mutex<History>);
+
+type HistoryStateResult<'a> = Result<MutexGuard<'a, History>, PoisonError<MutexGuard<'a, History>>>;
+
+impl HistoryState {
+    pub fn new(history: History) -> Self {
+        Self(Mutex::new(history))
+    }
+    pub fn get_state(&self) -> HistoryStateResult<'_> {
+        self.0.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::store::HistoryStore;
+
+    fn mems(rollups: Vec<Rollup>) -> Vec<u64> {
+        rollups.iter().map(|rollup| rollup.avg.mem).collect()
+    }
+
+    fn sample(timestamp: u64, mem: u64) -> SystemUtilization {
+        SystemUtilization {
+            timestamp,
+            mem,
+            cpus: vec![CpuCore {
+                usage: mem as f32,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn push_test() {
+        let mut history = History::with_capacity(3);
+        for i in 0..5 {
+            history.push(sample(i * 1000, i));
+        }
+        let mems: Vec<_> = history.samples.iter().map(|util| util.mem).collect();
+        assert_eq!(vec![2, 3, 4], mems);
+    }
+
+    #[test]
+    fn range_test() {
+        let mut history = History::with_capacity(10);
+        // Sampling jitters by a few milliseconds
+        for (timestamp, mem) in [(1003, 1), (1998, 2), (3001, 3), (4000, 4), (5002, 5)] {
+            history.push(sample(timestamp, mem));
+        }
+
+        assert_eq!(vec![1, 2, 3, 4, 5], mems(history.range(0, 5002, 1000)));
+        assert_eq!(vec![3, 4], mems(history.range(2000, 4000, 1000)));
+        // Buckets are (5002), (4000, 3001) and (1998, 1003), means are rounded
+        assert_eq!(vec![2, 4, 5], mems(history.range(0, 5002, 2000)));
+
+        let averaged = history.range(1998, 5002, 5000);
+        assert_eq!(1, averaged.len());
+        assert_eq!(4, averaged[0].avg.mem);
+        assert_eq!(4.0, averaged[0].avg.cpus[0].usage);
+        assert_eq!(4001, averaged[0].avg.timestamp);
+        assert_eq!(3, averaged[0].min.mem);
+        assert_eq!(5, averaged[0].max.mem);
+
+        assert!(history.range(6000, 7000, 1000).is_empty());
+    }
+
+    #[test]
+    fn tier_test() {
+        let mut tier = Tier::new(Duration::from_secs(10), DAY);
+        for (timestamp, mem) in [(10_000, 4), (14_000, 2), (19_999, 6)] {
+            assert!(tier.add(&sample(timestamp, mem)).is_none());
+        }
+        let rollup = tier.add(&sample(20_000, 1)).unwrap();
+        assert_eq!(2, rollup.min.mem);
+        assert_eq!(4, rollup.avg.mem);
+        assert_eq!(6, rollup.max.mem);
+        assert_eq!(10_000, rollup.min.timestamp);
+        assert_eq!(19_999, rollup.max.timestamp);
+        assert_eq!(1, tier.rollups.len());
+
+        // Gaps don't produce empty rollups
+        assert!(tier.add(&sample(95_000, 1)).is_some());
+        assert_eq!(2, tier.rollups.len());
+    }
+
+    #[test]
+    fn pending_test() {
+        let mut tier = Tier::new(Duration::from_secs(10), DAY);
+        let interface = |name: &str, down| NetworkInterface {
+            name: name.to_owned(),
+            mac: name.to_owned(),
+            down,
+            ..Default::default()
+        };
+        for (timestamp, interfaces) in [
+            (10_000, vec![interface("eth0", 10)]),
+            (12_000, vec![interface("eth0", 20)]),
+            // Came up partway through the bucket
+            (14_000, vec![interface("eth0", 60), interface("tun0", 100)]),
+        ] {
+            let mut sample = sample(timestamp, 0);
+            sample.network.interfaces = interfaces;
+            tier.add(&sample);
+        }
+        let rollup = tier.add(&sample(20_000, 0)).unwrap();
+        let averages: Vec<_> = rollup
+            .avg
+            .network
+            .interfaces
+            .iter()
+            .map(|interface| (interface.name.as_str(), interface.down))
+            .collect();
+        assert_eq!(vec![("eth0", 30), ("tun0", 100)], averages);
+        assert_eq!(12_000, rollup.avg.timestamp);
+        assert_eq!(10, rollup.min.network.interfaces[0].down);
+        // Only the labels the interfaces are matched by are kept
+        assert!(tier.rollups[0].max.network.interfaces[1].mac.is_empty());
+    }
+
+    #[test]
+    fn range_from_tier_test() {
+        let mut history = History::with_capacity(10);
+        // One sample every 10 seconds for two hours, so that the raw samples don't cover it
+        let completed: Vec<_> = (0..720)
+            .flat_map(|i| history.push(sample(i * 10_000 + 5_000, i % 2 * 100)))
+            .collect();
+        assert_eq!(719, completed.iter().filter(|(tier, _)| *tier == 0).count());
+        assert_eq!(23, completed.iter().filter(|(tier, _)| *tier == 1).count());
+
+        let to = 719 * 10_000 + 5_000;
+        let averaged = history.range(0, to, 600_000);
+        // Centered on `to`, so the first and last buckets are half as long
+        assert_eq!(13, averaged.len());
+        assert!(averaged.iter().all(|rollup| rollup.avg.mem == 50));
+        // Spikes survive in the envelope
+        assert!(averaged.iter().all(|rollup| rollup.min.mem == 0));
+        assert!(averaged.iter().all(|rollup| rollup.max.mem == 100));
+        // The most recent sample is still in the pending bucket
+        let latest = history.range(0, to, 1);
+        assert_eq!(100, latest.last().unwrap().avg.mem);
+
+        assert_eq!(10_000, history.finest_resolution(0, to));
+        assert_eq!(1000, history.finest_resolution(to - 60_000, to));
+    }
+
+    #[test]
+    fn saved_tier_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig {
+            dir: dir.path().to_owned(),
+            max_age: DAY,
+            max_size: u64::MAX,
+        };
+        let mut history = History::with_capacity(10);
+        let mut store = HistoryStore::open(config, &mut history, 0).unwrap();
+        let mut push = |history: &mut History, sample: SystemUtilization| {
+            let rollups = history.push(sample.clone());
+            store.append(&sample, &rollups).unwrap();
+        };
+        for i in 0..720 {
+            push(&mut history, sample(i * 10_000 + 5_000, i % 2 * 100));
+        }
+        // Completed buckets are left to the store until a period they're in is shown
+        assert!(history.tiers.iter().all(|tier| tier.rollups.is_empty()));
+
+        let to = 719 * 10_000 + 5_000;
+        let averaged = history.range(0, to, 600_000);
+        assert_eq!(13, averaged.len());
+        assert!(averaged.iter().all(|rollup| rollup.avg.mem == 50));
+        assert_eq!(719, history.tiers[0].rollups.len());
+        assert!(history.tiers[1].rollups.is_empty());
+        // Kept up to date while shown
+        push(&mut history, sample(to + 10_000, 0));
+        assert_eq!(720, history.tiers[0].rollups.len());
+
+        history.range(to - 60_000, to, 1000);
+        assert!(history.tiers[0].rollups.is_empty());
+    }
+
+    #[test]
+    fn restore_test() {
+        let mut history = History::with_capacity(10);
+        let samples: Vec<_> = (0..5).map(|i| sample(i * 4_000, i)).collect();
+        // The first 10 seconds were rolled up before quitting, the rest weren't
+        let rollup = Rollup {
+            min: sample(0, 0),
+            avg: sample(4_000, 1),
+            max: sample(8_000, 2),
+            count: 3,
+        };
+        history.restore(samples, vec![vec![rollup], Vec::new(), Vec::new()]);
+
+        assert_eq!(5, history.samples.len());
+        let tier = &history.tiers[0];
+        assert_eq!(1, tier.rollups.len());
+        let pending = tier.pending.as_ref().unwrap();
+        assert_eq!(1, pending.bucket);
+        // The samples at 12s and 16s
+        assert_eq!(2, pending.counts.mem);
+        assert_eq!(4, pending.rollup().avg.mem);
+        assert_eq!(2, pending.rollup().count);
+        assert!(history.tiers[1].rollups.is_empty());
+        assert_eq!(5, history.tiers[1].pending.as_ref().unwrap().counts.mem);
+    }
+}
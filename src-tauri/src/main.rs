@@ -1,222 +1,242 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod diskstats;
+mod export;
+mod filesystems;
+mod gpu;
+mod headless;
+mod history;
+mod meminfo;
+mod metrics;
+mod monitor;
+mod network;
+mod processes;
+mod procstat;
+mod recording;
+mod sampler;
+mod sensors;
+mod store;
+#[cfg(test)]
+mod test_util;
+mod tui;
+
 use shared::*;
 
 use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
     process,
-    sync::{Mutex, MutexGuard, OnceLock, PoisonError},
-    time::{Duration, Instant},
+    sync::OnceLock,
+    time::{Duration, SystemTime},
 };
 
-use nvml_wrapper::{Nvml, enum_wrappers::device::TemperatureSensor};
+use export::Format;
+use filesystems::FilesystemFilter;
+use headless::Output;
+use history::{History, HistoryState};
+use monitor::{MonitorConfig, SystemMonitor, SystemMonitorState};
+use network::InterfaceFilter;
+use recording::ReplayState;
+use store::{HistoryStore, StoreConfig};
 
-struct SystemMonitor {
-    nvml: Option<Nvml>,
-    sys: sysinfo::System,
-    sys_info: SystemInfo,
-    networks: sysinfo::Networks,
-    last_update: Instant,
+use clap::{CommandFactory, Parser, Subcommand};
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct CliArgs {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(long, default_value_t = false, help = "Start minimized to tray")]
+    minimize: bool,
+    #[arg(long, help = "Set window width")]
+    width: Option<u32>,
+    #[arg(long, help = "Set window height")]
+    height: Option<u32>,
+    #[arg(long, help = "Generate shell completions")]
+    completions: Option<clap_complete::Shell>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values = filesystems::DEFAULT_EXCLUDED_TYPES,
+        help = "Filesystem types hidden from the filesystem list"
+    )]
+    fs_exclude_types: Vec<String>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values = filesystems::DEFAULT_EXCLUDED_MOUNT_POINTS,
+        help = "Mount point prefixes hidden from the filesystem list"
+    )]
+    fs_exclude_mounts: Vec<String>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Network interfaces counted in the network total [default: all] (supports wildcards)"
+    )]
+    net_include: Vec<String>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values = network::DEFAULT_EXCLUDED_INTERFACES,
+        help = "Network interfaces left out of the network total (supports `*` and `?` wildcards)"
+    )]
+    net_exclude: Vec<String>,
+    #[arg(
+        long,
+        default_value_t = 0,
+        hide = true,
+        help = "Replace GPUs with fake ones"
+    )]
+    mock_gpus: usize,
+    #[arg(long, default_value_t = false, help = "Don't save history to disk")]
+    no_history: bool,
+    #[arg(
+        long,
+        help = "Directory of the saved history [default: $XDG_DATA_HOME/resource-monitor/history]"
+    )]
+    history_dir: Option<PathBuf>,
+    #[arg(long, default_value_t = 365, help = "Days of history kept on disk")]
+    history_retention: u64,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Disk space used by the history at most, in MiB"
+    )]
+    history_max_size: u64,
+    #[arg(
+        long,
+        help = "Export the saved history to a file and exit, with columns like `cpu.3.usage`"
+    )]
+    export: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Format of the export [default: guessed from the file extension]"
+    )]
+    export_format: Option<Format>,
+    #[arg(
+        long,
+        default_value_t = 3600,
+        help = "Seconds of history exported, up to now"
+    )]
+    export_period: u64,
+    #[arg(
+        long,
+        help = "Replay a recording made with `record` instead of monitoring the system"
+    )]
+    replay: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        // Nothing samples the system while replaying, so there would be nothing to serve
+        conflicts_with = "replay",
+        help = "Serve Prometheus metrics at http://<ADDRESS>/metrics, e.g. 127.0.0.1:9184"
+    )]
+    metrics: Option<SocketAddr>,
 }
 
-struct SystemMonitorState(Mutex<SystemMonitor>);
-
-type SystemMonitorStateResult<'a> =
-    Result<MutexGuard<'a, SystemMonitor>, PoisonError<MutexGuard<'a, SystemMonitor>>>;
+#[derive(Subcommand)]
+enum Command {
+    /// Record samples to a file without opening a window, to replay them later with `--replay`
+    Record {
+        path: PathBuf,
+        #[arg(long, help = "Seconds to record for [default: until interrupted]")]
+        duration: Option<u64>,
+    },
+    /// Print samples to stdout without opening a window, e.g. in scripts and CI jobs
+    Sample {
+        #[arg(long, value_enum, default_value_t = Output::Table)]
+        format: Output,
+        #[arg(
+            long,
+            default_value_t = 1,
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "Seconds between samples"
+        )]
+        interval: u64,
+        #[arg(
+            long,
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "Number of samples to print [default: until interrupted]"
+        )]
+        count: Option<u64>,
+    },
+    /// Show the system in the terminal, e.g. over SSH
+    Tui,
+}
 
-impl SystemMonitorState {
-    fn new() -> Self {
-        Self(Mutex::new(SystemMonitor::new()))
-    }
-    fn get_state(&self) -> SystemMonitorStateResult<'_> {
-        self.0.lock()
+impl CliArgs {
+    fn monitor_config(&self) -> MonitorConfig {
+        MonitorConfig {
+            filesystem_filter: FilesystemFilter {
+                excluded_types: self.fs_exclude_types.clone(),
+                excluded_mount_points: self.fs_exclude_mounts.clone(),
+            },
+            interface_filter: InterfaceFilter {
+                include: self.net_include.clone(),
+                exclude: self.net_exclude.clone(),
+            },
+            mock_gpus: self.mock_gpus,
+            metrics_address: self.metrics,
+        }
     }
-}
 
-impl SystemMonitor {
-    fn new() -> Self {
-        let sys = sysinfo::System::new_all();
-
-        let cpu_brand = sys
-            .cpus()
-            .first()
-            .map_or("", sysinfo::Cpu::brand)
-            .to_owned();
-        let cpu_core_count = sys.cpus().len() as u32;
-        let max_mem = sys.total_memory();
-
-        let (gpu_count, gpu_names) = if let Ok(nvml) = Nvml::init() {
-            let gpu_count = nvml.device_count().unwrap_or(0);
-            let gpu_names = (0..gpu_count)
-                .map(|gpu_id| {
-                    nvml.device_by_index(gpu_id)
-                        .map_or("".to_owned(), |device| {
-                            device.name().unwrap_or("".to_owned())
-                        })
-                })
-                .collect();
-            (gpu_count, gpu_names)
-        } else {
-            (0, Vec::new())
-        };
-
-        let (cache_l1, cache_l2, cache_l3) = {
-            #[cfg(all(target_arch = "x86_64", not(target_env = "sgx")))]
-            {
-                let cpuid = raw_cpuid::CpuId::new();
-                let l1 = cpuid
-                    .get_l1_cache_and_tlb_info()
-                    .map(|info| info.dcache_size());
-                let (l2, l3) = cpuid
-                    .get_l2_l3_cache_and_tlb_info()
-                    .map_or((None, None), |info| {
-                        (Some(info.l2cache_size()), Some(info.l3cache_size()))
-                    });
-                (l1, l2, l3)
-            }
-            #[cfg(not(all(target_arch = "x86_64", not(target_env = "sgx"))))]
-            {
-                (None, None, None)
-            }
-        };
-
-        let sys_info = SystemInfo {
-            cpu_brand,
-            cpu_core_count,
-            cache_l1,
-            cache_l2,
-            cache_l3,
-            max_mem,
-            gpu_count,
-            gpu_names,
-        };
-
-        Self {
-            sys,
-            nvml: Nvml::init().ok(),
-            sys_info,
-            networks: sysinfo::Networks::new_with_refreshed_list(),
-            last_update: Instant::now(),
+    fn store_config(&self) -> Option<StoreConfig> {
+        if self.no_history {
+            return None;
         }
+        Some(StoreConfig {
+            dir: self.history_dir.clone().or_else(StoreConfig::default_dir)?,
+            max_age: Duration::from_secs(self.history_retention * 24 * 60 * 60),
+            max_size: self.history_max_size << 20,
+        })
     }
+}
 
-    fn get_stats(&mut self) -> SystemUtilization {
-        self.sys.refresh_cpu_specifics(
-            sysinfo::CpuRefreshKind::nothing()
-                .with_cpu_usage()
-                .with_frequency(),
-        );
-        self.sys
-            .refresh_processes(sysinfo::ProcessesToUpdate::All, true);
-        self.sys.refresh_memory();
-
-        let cpus = self
-            .sys
-            .cpus()
-            .iter()
-            .map(|cpu| CpuCore {
-                usage: cpu.cpu_usage(),
-                freq: cpu.frequency(),
-            })
-            .collect();
-
-        let processes = self.sys.processes().len() as u32;
-        let mem = self.sys.used_memory();
-        let mem_max = self.sys.total_memory();
-        let up_time = Duration::from_secs(sysinfo::System::uptime());
-
-        let gpus = if let Some(nvml) = &self.nvml {
-            let mut gpus_util = Vec::new();
-            let device_count = nvml.device_count().unwrap_or(0);
-            for gpu_idx in 0..device_count {
-                let (usage, mem, max_mem, temp) = if let Ok(gpu) = nvml.device_by_index(gpu_idx) {
-                    let (util, mem) = gpu
-                        .utilization_rates()
-                        .map_or((0, 0), |util| (util.gpu, util.memory));
-                    let temp = gpu.temperature(TemperatureSensor::Gpu).unwrap_or(0);
-                    let max_mem = gpu.memory_info().map_or(0, |mem_info| mem_info.total);
-                    (util, mem, max_mem, temp)
-                } else {
-                    (0, 0, 0, 0)
-                };
-                let gpu_util = Gpu {
-                    usage,
-                    mem,
-                    max_mem,
-                    temp,
-                };
-                gpus_util.push(gpu_util);
-            }
-            gpus_util
-        } else {
-            vec![]
-        };
-
-        let disk = {
-            let (read, written) =
-                self.sys
-                    .processes()
-                    .iter()
-                    .fold((0, 0), |(read, write), (_pid, proc)| {
-                        (
-                            read + proc.disk_usage().read_bytes,
-                            write + proc.disk_usage().written_bytes,
-                        )
-                    });
-            Disk {
-                read_bytes: read,
-                writen_bytes: written,
-            }
-        };
-
-        let network = {
-            self.networks.refresh(true);
-            let (down, up) = self
-                .networks
-                .iter()
-                .fold((0, 0), |(down, up), (_inteface, data)| {
-                    (down + data.received(), up + data.transmitted())
-                });
-
-            let update_time = Instant::now();
-            let duration = update_time.duration_since(self.last_update);
-            self.last_update = update_time;
-            Network {
-                down: (down as f32 / duration.as_secs_f32()) as u64,
-                up: (up as f32 / duration.as_secs_f32()) as u64,
-            }
-        };
-
-        SystemUtilization {
-            cpus,
-            mem,
-            processes,
-            mem_max,
-            disk,
-            gpus,
-            up_time,
-            network,
+/// In milliseconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_millis() as u64)
+}
+
+/// Opens the on-disk store and loads the history it holds
+fn load_history(config: Option<StoreConfig>) -> (History, Option<HistoryStore>) {
+    let mut history = History::new();
+    let Some(config) = config else {
+        return (history, None);
+    };
+    match HistoryStore::open(config, &mut history, now()) {
+        Ok(store) => (history, Some(store)),
+        Err(err) => {
+            eprintln!("Failed to open the history store, history won't be saved: {err}");
+            (history, None)
         }
     }
 }
 
-use clap::{CommandFactory, Parser};
-#[derive(Parser)]
-#[command(author, version, about, long_about = None)]
-struct CliArgs {
-    #[arg(long, default_value_t = false, help = "Start minimized to tray")]
-    minimize: bool,
-    #[arg(long, help = "Set window width")]
-    width: Option<u32>,
-    #[arg(long, help = "Set window height")]
-    height: Option<u32>,
-    #[arg(long, help = "Generate shell completions")]
-    completions: Option<clap_complete::Shell>,
+/// Loads the saved history without writing to it, as the app may be running and saving it
+fn read_history(config: Option<StoreConfig>) -> History {
+    let mut history = History::new();
+    if let Some(config) = config
+        && let Err(err) = HistoryStore::read(&config, &mut history, now())
+    {
+        eprintln!("Failed to read the history store: {err}");
+    }
+    history
 }
 
-#[tauri::command]
-fn get_stats(state: tauri::State<SystemMonitorState>) -> SystemUtilization {
-    state.get_state().unwrap().get_stats()
+/// Exports the saved history as `--export` asks, without starting the app
+fn export_saved_history(cli_args: &CliArgs, path: &Path) -> Result<(), String> {
+    let format = cli_args
+        .export_format
+        .or_else(|| Format::from_path(path))
+        .ok_or("Unknown export format, pass it with --export-format")?;
+    let mut history = read_history(cli_args.store_config());
+    let to = now();
+    let from = to.saturating_sub(cli_args.export_period * 1000);
+    let rollups = history.finest_range(from, to);
+    export::export(rollups.iter().map(|rollup| &rollup.avg), format, path)
+        .map_err(|err| format!("Failed to export the history: {err}"))
 }
 
 #[tauri::command]
@@ -224,6 +244,116 @@ fn get_sys_info(state: tauri::State<SystemMonitorState>) -> SystemInfo {
     state.get_state().unwrap().sys_info.clone()
 }
 
+/// Samples taken after `from` and up to `to` (in milliseconds since the Unix epoch),
+/// rolled up over `resolution` milliseconds
+#[tauri::command]
+fn get_history(
+    state: tauri::State<HistoryState>,
+    from: u64,
+    to: u64,
+    resolution: u64,
+) -> Vec<Rollup> {
+    state.get_state().unwrap().range(from, to, resolution)
+}
+
+/// Exports the samples taken after `from` and up to `to` to the downloads directory.
+/// Returns the path of the file.
+#[tauri::command]
+fn export_history(
+    state: tauri::State<HistoryState>,
+    from: u64,
+    to: u64,
+    format: Format,
+) -> Result<PathBuf, String> {
+    let dir = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .ok_or("No downloads directory")?;
+    let name = format!(
+        "{}-{}.{}",
+        env!("CARGO_PKG_NAME"),
+        to / 1000,
+        format.extension()
+    );
+    let path = dir.join(name);
+    // Released before writing, so that sampling doesn't wait for the file
+    let rollups = state.get_state().unwrap().finest_range(from, to);
+    export::export(rollups.iter().map(|rollup| &rollup.avg), format, &path)
+        .map_err(|err| err.to_string())?;
+    Ok(path)
+}
+
+/// When the first and the last sample of the recording being replayed were taken,
+/// `None` when monitoring the system
+#[tauri::command]
+fn get_replay_range(state: tauri::State<ReplayState>) -> Option<(u64, u64)> {
+    state.range()
+}
+
+/// Recorded samples taken after `from` and up to `to`, in milliseconds since the Unix epoch
+#[tauri::command]
+fn get_recording(state: tauri::State<ReplayState>, from: u64, to: u64) -> Vec<SystemUtilization> {
+    state.samples(from, to).to_vec()
+}
+
+#[tauri::command]
+fn get_processes(state: tauri::State<SystemMonitorState>) -> Vec<ProcessInfo> {
+    state.get_state().unwrap().get_processes()
+}
+
+#[tauri::command]
+fn get_gpu_processes(state: tauri::State<SystemMonitorState>) -> Vec<GpuProcess> {
+    state.get_state().unwrap().get_gpu_processes()
+}
+
+#[tauri::command]
+fn get_filesystems(state: tauri::State<SystemMonitorState>) -> Vec<Filesystem> {
+    state.get_state().unwrap().get_filesystems()
+}
+
+#[tauri::command]
+fn terminate_process(
+    state: tauri::State<SystemMonitorState>,
+    pid: u32,
+) -> Result<(), ProcessActionError> {
+    state
+        .get_state()
+        .unwrap()
+        .signal_process(pid, ProcessSignal::Terminate)
+}
+
+#[tauri::command]
+fn kill_process(
+    state: tauri::State<SystemMonitorState>,
+    pid: u32,
+) -> Result<(), ProcessActionError> {
+    state
+        .get_state()
+        .unwrap()
+        .signal_process(pid, ProcessSignal::Kill)
+}
+
+#[tauri::command]
+fn suspend_process(
+    state: tauri::State<SystemMonitorState>,
+    pid: u32,
+) -> Result<(), ProcessActionError> {
+    state
+        .get_state()
+        .unwrap()
+        .signal_process(pid, ProcessSignal::Suspend)
+}
+
+#[tauri::command]
+fn resume_process(
+    state: tauri::State<SystemMonitorState>,
+    pid: u32,
+) -> Result<(), ProcessActionError> {
+    state
+        .get_state()
+        .unwrap()
+        .signal_process(pid, ProcessSignal::Resume)
+}
+
 use tauri::{
     AppHandle, Manager, PhysicalSize, RunEvent, Size, WindowEvent, Wry,
     menu::{MenuBuilder, MenuItem, MenuItemBuilder},
@@ -261,6 +391,67 @@ fn main() {
         return;
     }
 
+    if let Some(path) = &cli_args.export {
+        if let Err(err) = export_saved_history(&cli_args, path) {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Record { path, duration }) = &cli_args.command {
+        let mut monitor = SystemMonitor::new(cli_args.monitor_config());
+        let duration = duration.map(Duration::from_secs);
+        if let Err(err) = recording::record(&mut monitor, path, duration) {
+            eprintln!("Failed to record: {err}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Sample {
+        format,
+        interval,
+        count,
+    }) = &cli_args.command
+    {
+        let mut monitor = SystemMonitor::new(cli_args.monitor_config());
+        let interval = Duration::from_secs(*interval);
+        if let Err(err) = headless::sample(&mut monitor, *format, interval, *count) {
+            eprintln!("Failed to sample: {err}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Tui) = &cli_args.command {
+        let mut monitor = SystemMonitor::new(cli_args.monitor_config());
+        // Shows the saved history but leaves saving to the app
+        let history = read_history(cli_args.store_config());
+        if let Err(err) = tui::run(&mut monitor, history) {
+            eprintln!("Failed to run the terminal UI: {err}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    let replay = match &cli_args.replay {
+        Some(path) => match recording::read(path) {
+            Ok(samples) => ReplayState::new(Some(samples)),
+            Err(err) => {
+                eprintln!("Failed to open the recording: {err}");
+                process::exit(1);
+            }
+        },
+        None => ReplayState::new(None),
+    };
+    let replaying = replay.is_replaying();
+    let (history, store) = if replaying {
+        (replay.history(), None)
+    } else {
+        load_history(cli_args.store_config())
+    };
+
     let builder = if cfg!(not(debug_assertions)) {
         tauri::Builder::default()
             .plugin(tauri_plugin_shell::init())
@@ -274,7 +465,9 @@ fn main() {
 
     #[allow(clippy::single_match)]
     builder
-        .manage(SystemMonitorState::new())
+        .manage(SystemMonitorState::new(cli_args.monitor_config()))
+        .manage(HistoryState::new(history))
+        .manage(replay)
         .setup(move |app| {
             TRAY_SHOW.get_or_init(|| {
                 MenuItemBuilder::with_id(TRAY_SHOW_ID, "Show")
@@ -328,6 +521,10 @@ fn main() {
                 window.set_size(size).unwrap();
             }
 
+            if !replaying {
+                sampler::spawn(app.handle().clone(), store);
+            }
+
             Ok(())
         })
         .on_window_event(|window, event| {
@@ -336,7 +533,20 @@ fn main() {
                 api.prevent_close();
             }
         })
-        .invoke_handler(tauri::generate_handler![get_stats, get_sys_info])
+        .invoke_handler(tauri::generate_handler![
+            get_sys_info,
+            get_history,
+            export_history,
+            get_replay_range,
+            get_recording,
+            get_processes,
+            get_gpu_processes,
+            get_filesystems,
+            terminate_process,
+            kill_process,
+            suspend_process,
+            resume_process
+        ])
         .build(tauri::generate_context!())
         .expect("error while running tauri application")
         .run(|_app, event| {
@@ -0,0 +1,131 @@
+use shared::Memory;
+
+use std::{collections::HashMap, fs, time::Instant};
+
+/// `/proc/meminfo` values in bytes, keyed by field name
+pub fn parse_meminfo(text: &str) -> HashMap<&str, u64> {
+    text.lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let mut value = value.split_whitespace();
+            let amount: u64 = value.next()?.parse().ok()?;
+            let multiplier = match value.next() {
+                Some("kB") => 1024,
+                _ => 1,
+            };
+            Some((key, amount * multiplier))
+        })
+        .collect()
+}
+
+/// Pages swapped in and out since boot, from `/proc/vmstat`
+pub fn parse_vmstat_swap(text: &str) -> Option<(u64, u64)> {
+    let counter = |name: &str| {
+        text.lines().find_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            if key == name {
+                value.trim().parse().ok()
+            } else {
+                None
+            }
+        })
+    };
+    Some((counter("pswpin")?, counter("pswpout")?))
+}
+
+pub struct MemoryCollector {
+    page_size: u64,
+    /// Swapped pages as of the previous refresh
+    previous_swap: Option<(u64, u64)>,
+    last_update: Instant,
+}
+
+impl MemoryCollector {
+    pub fn new() -> Self {
+        #[cfg(unix)]
+        // SAFETY: `sysconf` has no preconditions
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }
+            .try_into()
+            .unwrap_or(4096);
+        #[cfg(not(unix))]
+        let page_size = 4096;
+
+        Self {
+            page_size,
+            previous_swap: fs::read_to_string("/proc/vmstat")
+                .ok()
+                .and_then(|text| parse_vmstat_swap(&text)),
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Expects `sys`'s memory to be freshly refreshed.
+    pub fn refresh(&mut self, sys: &sysinfo::System) -> Memory {
+        let update_time = Instant::now();
+        let secs = update_time
+            .duration_since(self.last_update)
+            .as_secs_f32()
+            .max(f32::EPSILON);
+        self.last_update = update_time;
+
+        let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
+        let meminfo = parse_meminfo(&meminfo);
+        let field = |name| meminfo.get(name).copied().unwrap_or(0);
+
+        let swap = fs::read_to_string("/proc/vmstat")
+            .ok()
+            .and_then(|text| parse_vmstat_swap(&text));
+        let (swap_in, swap_out) = match (self.previous_swap, swap) {
+            (Some((prev_in, prev_out)), Some((curr_in, curr_out))) => {
+                let rate = |prev: u64, curr: u64| {
+                    (curr.saturating_sub(prev) as f32 * self.page_size as f32 / secs) as u64
+                };
+                (rate(prev_in, curr_in), rate(prev_out, curr_out))
+            }
+            _ => (0, 0),
+        };
+        self.previous_swap = swap;
+
+        Memory {
+            available: sys.available_memory(),
+            free: sys.free_memory(),
+            cached: field("Cached") + field("SReclaimable"),
+            buffers: field("Buffers"),
+            shared: field("Shmem"),
+            swap_used: sys.used_swap(),
+            swap_total: sys.total_swap(),
+            swap_in,
+            swap_out,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_meminfo_test() {
+        let meminfo = "\
+MemTotal:       32567084 kB
+MemFree:         1234567 kB
+Buffers:          345678 kB
+Cached:          8765432 kB
+Shmem:            456789 kB
+SReclaimable:     567890 kB
+HugePages_Total:       0
+";
+        let meminfo = parse_meminfo(meminfo);
+        assert_eq!(Some(&(32567084 * 1024)), meminfo.get("MemTotal"));
+        assert_eq!(Some(&(345678 * 1024)), meminfo.get("Buffers"));
+        assert_eq!(Some(&0), meminfo.get("HugePages_Total"));
+        assert_eq!(None, meminfo.get("SwapTotal"));
+    }
+
+    #[test]
+    fn parse_vmstat_swap_test() {
+        let vmstat = "nr_free_pages 123\npswpin 42\npswpout 1337\npgfault 5\n";
+        assert_eq!(Some((42, 1337)), parse_vmstat_swap(vmstat));
+        assert_eq!(None, parse_vmstat_swap("nr_free_pages 123\n"));
+    }
+}
@@ -0,0 +1,460 @@
+use shared::{Columns, Kind, Number, SystemInfo, SystemUtilization};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Write},
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use tiny_http::{Header, Method, Response, Server};
+
+/// Prefix of every metric name
+const NAMESPACE: &str = "resource_monitor";
+
+/// Samples of a single metric, which the text format wants together
+struct Family {
+    name: String,
+    help: String,
+    kind: Kind,
+    /// Rendered labels and value of every sample
+    samples: Vec<(String, String)>,
+}
+
+/// Metrics in the Prometheus text format, in the order they were first added
+#[derive(Default)]
+struct Metrics {
+    families: Vec<Family>,
+    indices: HashMap<String, usize>,
+    /// Name, rendered labels and value of every [`Kind::Rate`], to add up into counters
+    rates: Vec<(String, String, f64)>,
+}
+
+/// Turns a column name like `cpu_times.user` into a valid metric name.
+/// Counters end in `_total`, as Prometheus expects.
+fn metric_name(name: &str, kind: Kind) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match kind {
+        Kind::Counter => format!("{NAMESPACE}_{name}_total"),
+        _ => format!("{NAMESPACE}_{name}"),
+    }
+}
+
+/// What a metric made of a column is, e.g. `disk.read_bytes per second`
+fn column_help(name: &str, kind: Kind) -> String {
+    match kind {
+        Kind::Gauge => name.to_owned(),
+        Kind::Rate => format!("{name} per second"),
+        Kind::Constant => format!("{name}, fixed while running"),
+        Kind::Counter => format!("{name} so far"),
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    labels
+        .iter()
+        .map(|(label, value)| format!(r#"{label}="{}""#, escape_label(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl Metrics {
+    fn push(&mut self, name: String, help: &str, kind: Kind, labels: String, value: String) {
+        let index = *self.indices.entry(name).or_insert_with_key(|name| {
+            self.families.push(Family {
+                name: name.clone(),
+                help: help.to_owned(),
+                kind,
+                samples: Vec::new(),
+            });
+            self.families.len() - 1
+        });
+        self.families[index].samples.push((labels, value));
+    }
+
+    fn add(&mut self, name: &str, help: &str, labels: &[(&str, &str)], value: impl Display) {
+        let name = metric_name(name, Kind::Gauge);
+        self.push(
+            name,
+            help,
+            Kind::Gauge,
+            render_labels(labels),
+            value.to_string(),
+        );
+    }
+
+    /// Adds every column of `columns` under `prefix`, with the same `labels`
+    fn add_columns(&mut self, columns: &impl Columns, prefix: &str, labels: &[(&str, &str)]) {
+        let labels = render_labels(labels);
+        columns.columns(prefix, &mut |column, kind, value| {
+            let name = metric_name(&column, kind);
+            if kind == Kind::Rate {
+                let rate = match value {
+                    Number::Integer(value) => value as f64,
+                    Number::Float(value) => value as f64,
+                };
+                self.rates.push((name.clone(), labels.clone(), rate));
+            }
+            let help = column_help(&column, kind);
+            self.push(name, &help, kind, labels.clone(), value.to_string());
+        });
+    }
+
+    fn add_info(&mut self, sys_info: &SystemInfo) {
+        let SystemInfo {
+            cpu_brand,
+            cpu_core_count,
+            cache_l1,
+            cache_l2,
+            cache_l3,
+            max_mem,
+            gpu_count,
+            gpu_names,
+            gpu_pci_slots,
+        } = sys_info;
+        self.add(
+            "cpu_info",
+            "CPU model, always 1",
+            &[("brand", cpu_brand)],
+            1,
+        );
+        self.add("cpu_core_count", "Logical CPU cores", &[], cpu_core_count);
+        for (name, help, cache) in [
+            ("cache_l1", "L1 data cache in KB", cache_l1.map(u16::from)),
+            ("cache_l2", "L2 cache in KB", *cache_l2),
+            ("cache_l3", "L3 cache in KB", *cache_l3),
+        ] {
+            if let Some(cache) = cache {
+                self.add(name, help, &[], cache);
+            }
+        }
+        self.add("max_mem", "Total memory in bytes", &[], max_mem);
+        self.add("gpu_count", "GPUs found", &[], gpu_count);
+        for (i, name) in gpu_names.iter().enumerate() {
+            let pci_slot = gpu_pci_slots.get(i).map_or("", String::as_str);
+            let labels = [
+                ("gpu", &*i.to_string()),
+                ("name", name),
+                ("pci_slot", pci_slot),
+            ];
+            self.add("gpu_info", "GPU model and PCI slot, always 1", &labels, 1);
+        }
+    }
+
+    /// Lists become labeled metrics, e.g. `cpu_core_usage{cpu="3"}`, named apart from the totals
+    /// so that summing over a metric doesn't count anything twice
+    fn add_sample(&mut self, sample: &SystemUtilization) {
+        let mut totals = sample.clone();
+        totals.cpus.clear();
+        totals.disks.clear();
+        totals.gpus.clear();
+        totals.sensors.clear();
+        totals.network.interfaces.clear();
+        self.add_columns(&totals, "", &[]);
+
+        for (i, cpu) in sample.cpus.iter().enumerate() {
+            self.add_columns(cpu, "cpu_core", &[("cpu", &i.to_string())]);
+        }
+        for (i, gpu) in sample.gpus.iter().enumerate() {
+            let i = i.to_string();
+            self.add_columns(gpu, "gpu", &[("gpu", &i)]);
+            for reason in &gpu.throttle_reasons {
+                let labels = [("gpu", &*i), ("reason", reason)];
+                let help = "Reason the GPU is throttled, always 1";
+                self.add("gpu_throttle_reason", help, &labels, 1);
+            }
+        }
+        for disk in &sample.disks {
+            self.add_columns(disk, "disk_device", &[("device", &disk.name)]);
+        }
+        for interface in &sample.network.interfaces {
+            let name = &*interface.name;
+            self.add_columns(interface, "network_interface", &[("interface", name)]);
+            let labels = [
+                ("interface", name),
+                ("mac", &interface.mac),
+                ("included", &interface.included.to_string()),
+            ];
+            let help = "MAC of an interface and whether it counts towards totals, always 1";
+            self.add("network_interface_info", help, &labels, 1);
+            for ip in &interface.ips {
+                let labels = [("interface", name), ("address", ip)];
+                let help = "Address of an interface, always 1";
+                self.add("network_interface_address", help, &labels, 1);
+            }
+        }
+        for sensor in &sample.sensors {
+            let labels = [
+                ("chip", &*sensor.chip),
+                ("device", &sensor.device),
+                ("sensor", &sensor.label),
+            ];
+            self.add_columns(sensor, "sensor", &labels);
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut text = String::new();
+        for family in &self.families {
+            let kind = match family.kind {
+                Kind::Counter => "counter",
+                Kind::Gauge | Kind::Rate | Kind::Constant => "gauge",
+            };
+            writeln!(text, "# HELP {} {}", family.name, family.help).unwrap();
+            writeln!(text, "# TYPE {} {kind}", family.name).unwrap();
+            for (labels, value) in &family.samples {
+                if labels.is_empty() {
+                    writeln!(text, "{} {value}", family.name).unwrap();
+                } else {
+                    writeln!(text, "{}{{{labels}}} {value}", family.name).unwrap();
+                }
+            }
+        }
+        text
+    }
+}
+
+/// What the exporter has seen so far
+#[derive(Default)]
+struct State {
+    latest: Option<SystemUtilization>,
+    /// Every [`Kind::Rate`] added up since the exporter started, by name and rendered labels, so
+    /// that scrapes further apart than samples don't miss anything
+    totals: BTreeMap<(String, String), f64>,
+}
+
+impl State {
+    fn publish(&mut self, sample: &SystemUtilization) {
+        // Rates are over the time since the previous sample, unknown for the first one
+        let secs = self.latest.as_ref().map_or(0.0, |previous| {
+            sample.monotonic.saturating_sub(previous.monotonic) as f64 / 1000.0
+        });
+        let mut metrics = Metrics::default();
+        metrics.add_sample(sample);
+        for (name, labels, rate) in metrics.rates {
+            *self.totals.entry((name, labels)).or_default() += rate * secs;
+        }
+        self.latest = Some(sample.clone());
+    }
+
+    /// Every field of `sys_info` and, once one is taken, of the latest sample
+    fn render(&self, sys_info: &SystemInfo) -> String {
+        let mut metrics = Metrics::default();
+        metrics.add_info(sys_info);
+        if let Some(sample) = &self.latest {
+            metrics.add_sample(sample);
+        }
+        for ((name, labels), total) in &self.totals {
+            let column = name
+                .strip_prefix(NAMESPACE)
+                .unwrap_or(name)
+                .trim_start_matches('_');
+            let help = format!("{column} added up since the exporter started");
+            let name = format!("{name}_total");
+            metrics.push(
+                name,
+                &help,
+                Kind::Counter,
+                labels.clone(),
+                total.round().to_string(),
+            );
+        }
+        metrics.render()
+    }
+}
+
+/// Serves the latest sample at `/metrics` in the Prometheus text format, until dropped
+pub struct MetricsExporter {
+    server: Arc<Server>,
+    state: Arc<Mutex<State>>,
+}
+
+impl MetricsExporter {
+    pub fn serve(address: SocketAddr, sys_info: SystemInfo) -> io::Result<Self> {
+        let server = Arc::new(Server::http(address).map_err(io::Error::other)?);
+        let state = Arc::new(Mutex::new(State::default()));
+        let content_type =
+            Header::from_bytes("Content-Type", "text/plain; version=0.0.4; charset=utf-8").unwrap();
+        thread::spawn({
+            let server = server.clone();
+            let state = state.clone();
+            move || {
+                for request in server.incoming_requests() {
+                    let path = request.url().split('?').next().unwrap_or_default();
+                    let response = if *request.method() == Method::Get && path == "/metrics" {
+                        let text = state.lock().unwrap().render(&sys_info);
+                        Response::from_string(text).with_header(content_type.clone())
+                    } else {
+                        Response::from_string("Not found").with_status_code(404)
+                    };
+                    // Nothing to do if the scraper is gone
+                    let _ = request.respond(response);
+                }
+            }
+        });
+        Ok(Self { server, state })
+    }
+
+    /// Where the metrics are served, e.g. the port picked for port 0
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Serves `sample` from now on
+    pub fn publish(&self, sample: &SystemUtilization) {
+        self.state.lock().unwrap().publish(sample);
+    }
+}
+
+impl Drop for MetricsExporter {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    use crate::test_util::sample;
+
+    fn sys_info() -> SystemInfo {
+        SystemInfo {
+            cpu_brand: "Test \"CPU\"".to_owned(),
+            cpu_core_count: 2,
+            cache_l1: Some(48),
+            max_mem: 16 << 30,
+            gpu_count: 1,
+            gpu_names: vec!["Test GPU".to_owned()],
+            gpu_pci_slots: vec!["0000:01:00.0".to_owned()],
+            ..Default::default()
+        }
+    }
+
+    fn lines(text: &str) -> Vec<&str> {
+        text.lines().collect()
+    }
+
+    #[test]
+    fn render_test() {
+        let mut state = State::default();
+        state.publish(&sample(1000));
+        let text = state.render(&sys_info());
+        let lines = lines(&text);
+        let expected = [
+            r#"resource_monitor_cpu_info{brand="Test \"CPU\""} 1"#,
+            "resource_monitor_cache_l1 48",
+            r#"resource_monitor_gpu_info{gpu="0",name="Test GPU",pci_slot="0000:01:00.0"} 1"#,
+            "resource_monitor_mem 3221225472",
+            "resource_monitor_cpu_times_user 0",
+            "# HELP resource_monitor_network_down network.down per second",
+            "# TYPE resource_monitor_network_down gauge",
+            "resource_monitor_network_down 2048",
+            "# TYPE resource_monitor_up_time_total counter",
+            "# TYPE resource_monitor_cpu_core_usage gauge",
+            r#"resource_monitor_cpu_core_usage{cpu="0"} 50"#,
+            r#"resource_monitor_cpu_core_usage{cpu="1"} 25"#,
+            r#"resource_monitor_cpu_core_times_user{cpu="1"} 0"#,
+            r#"resource_monitor_gpu_temp{gpu="0"} 60"#,
+            r#"resource_monitor_gpu_throttle_reason{gpu="0",reason="power cap"} 1"#,
+            "# TYPE resource_monitor_network_interface_errors_down_total counter",
+            r#"resource_monitor_network_interface_errors_down_total{interface="eth0"} 2"#,
+            r#"resource_monitor_network_interface_info{interface="eth0",mac="",included="true"} 1"#,
+            concat!(
+                r#"resource_monitor_network_interface_address{interface="eth0","#,
+                r#"address="192.168.1.2/24"} 1"#,
+            ),
+            r#"resource_monitor_sensor_temp{chip="coretemp",device="",sensor="Package id 0"} 45.5"#,
+            r#"resource_monitor_sensor_temp{chip="nvme",device="nvme1",sensor="Composite"} 40"#,
+        ];
+        for expected in expected {
+            assert!(lines.contains(&expected), "{expected} not in {text}");
+        }
+        // Samples of a metric follow its help and type
+        let usage = lines
+            .iter()
+            .position(|line| *line == "# TYPE resource_monitor_cpu_core_usage gauge")
+            .unwrap();
+        assert!(lines[usage - 1].starts_with("# HELP resource_monitor_cpu_core_usage "));
+        assert!(lines[usage + 1].starts_with("resource_monitor_cpu_core_usage{"));
+        assert!(lines[usage + 2].starts_with("resource_monitor_cpu_core_usage{"));
+        // Every metric has a help and a type
+        let count = |prefix| lines.iter().filter(|line| line.starts_with(prefix)).count();
+        assert_eq!(count("# HELP "), count("# TYPE "));
+        // Missing values aren't reported
+        assert!(!text.contains("resource_monitor_gpu_fan"));
+        assert!(!text.contains("resource_monitor_cache_l2"));
+
+        // Only the system's info until the first sample
+        let text = State::default().render(&sys_info());
+        assert!(text.contains("resource_monitor_max_mem 17179869184"));
+        assert!(!text.contains("resource_monitor_mem "));
+    }
+
+    #[test]
+    fn totals_test() {
+        let mut state = State::default();
+        for (monotonic, down) in [(1000, 2048), (2000, 1024), (4000, 512)] {
+            let mut sample = sample(1000);
+            sample.monotonic = monotonic;
+            sample.network.down = down;
+            sample.network.interfaces[0].down = down;
+            state.publish(&sample);
+        }
+        let text = state.render(&sys_info());
+        let lines = lines(&text);
+        // The first rate is over an unknown time, then 1024 for 1 s and 512 for 2 s
+        let expected = [
+            "# TYPE resource_monitor_network_down_total counter",
+            "resource_monitor_network_down_total 2048",
+            r#"resource_monitor_network_interface_down_total{interface="eth0"} 2048"#,
+            "resource_monitor_network_down 512",
+        ];
+        for expected in expected {
+            assert!(lines.contains(&expected), "{expected} not in {text}");
+        }
+    }
+
+    fn get(address: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {path} HTTP/1.0\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn scrape_test() {
+        let exporter = MetricsExporter::serve(([127, 0, 0, 1], 0).into(), sys_info()).unwrap();
+        let address = exporter.address().unwrap();
+
+        let response = get(address, "/metrics");
+        assert!(response.starts_with("HTTP/1.0 200"), "{response}");
+        assert!(response.contains("text/plain; version=0.0.4"));
+        assert!(response.contains("resource_monitor_gpu_count 1"));
+        assert!(!response.contains("resource_monitor_cpu_core_usage"));
+
+        exporter.publish(&sample(1000));
+        let response = get(address, "/metrics?name=cpu");
+        assert!(response.contains(r#"resource_monitor_cpu_core_usage{cpu="1"} 25"#));
+
+        assert!(get(address, "/").starts_with("HTTP/1.0 404"));
+    }
+}
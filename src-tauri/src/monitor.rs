@@ -0,0 +1,290 @@
+use shared::*;
+
+use std::{
+    net::SocketAddr,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    diskstats::DiskStatsCollector,
+    filesystems::{self, FilesystemFilter},
+    gpu::{GpuCollector, GpuProcessCollector},
+    meminfo::MemoryCollector,
+    metrics::MetricsExporter,
+    network::{self, InterfaceFilter},
+    processes,
+    procstat::{self, CpuTimesCollector},
+    sensors::SensorCollector,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct MonitorConfig {
+    pub filesystem_filter: FilesystemFilter,
+    pub interface_filter: InterfaceFilter,
+    /// Number of fake GPUs replacing the real ones, for testing
+    pub mock_gpus: usize,
+    /// Where to serve the latest sample as Prometheus metrics, if anywhere
+    pub metrics_address: Option<SocketAddr>,
+}
+
+pub struct SystemMonitor {
+    sys: sysinfo::System,
+    pub sys_info: SystemInfo,
+    networks: sysinfo::Networks,
+    users: sysinfo::Users,
+    diskstats: DiskStatsCollector,
+    gpus: GpuCollector,
+    gpu_processes: GpuProcessCollector,
+    disks: sysinfo::Disks,
+    memory: MemoryCollector,
+    cpu_times: CpuTimesCollector,
+    sensors: SensorCollector,
+    /// Publishes every sample
+    metrics: Option<MetricsExporter>,
+    config: MonitorConfig,
+    last_update: Instant,
+    /// Time between the two most recent refreshes, used to turn per-refresh counters into rates
+    update_interval: Duration,
+}
+
+pub struct SystemMonitorState(Mutex<SystemMonitor>);
+
+type SystemMonitorStateResult<'a> =
+    Result<MutexGuard<'a, SystemMonitor>, PoisonError<MutexGuard<'a, SystemMonitor>>>;
+
+impl SystemMonitorState {
+    pub fn new(config: MonitorConfig) -> Self {
+        Self(Mutex::new(SystemMonitor::new(config)))
+    }
+    pub fn get_state(&self) -> SystemMonitorStateResult<'_> {
+        self.0.lock()
+    }
+}
+impl SystemMonitor {
+    pub fn new(config: MonitorConfig) -> Self {
+        let sys = sysinfo::System::new_all();
+
+        let cpu_brand = sys
+            .cpus()
+            .first()
+            .map_or("", sysinfo::Cpu::brand)
+            .to_owned();
+        let cpu_core_count = sys.cpus().len() as u32;
+        let max_mem = sys.total_memory();
+
+        let gpus = GpuCollector::detect(config.mock_gpus);
+        let gpu_names = gpus.names();
+        let gpu_count = gpu_names.len() as u32;
+        let gpu_pci_slots = gpus.pci_slots();
+
+        let (cache_l1, cache_l2, cache_l3) = {
+            #[cfg(all(target_arch = "x86_64", not(target_env = "sgx")))]
+            {
+                let cpuid = raw_cpuid::CpuId::new();
+                let l1 = cpuid
+                    .get_l1_cache_and_tlb_info()
+                    .map(|info| info.dcache_size());
+                let (l2, l3) = cpuid
+                    .get_l2_l3_cache_and_tlb_info()
+                    .map_or((None, None), |info| {
+                        (Some(info.l2cache_size()), Some(info.l3cache_size()))
+                    });
+                (l1, l2, l3)
+            }
+            #[cfg(not(all(target_arch = "x86_64", not(target_env = "sgx"))))]
+            {
+                (None, None, None)
+            }
+        };
+
+        let sys_info = SystemInfo {
+            cpu_brand,
+            cpu_core_count,
+            cache_l1,
+            cache_l2,
+            cache_l3,
+            max_mem,
+            gpu_count,
+            gpu_names,
+            gpu_pci_slots,
+        };
+
+        let metrics = config.metrics_address.and_then(|address| {
+            match MetricsExporter::serve(address, sys_info.clone()) {
+                Ok(metrics) => Some(metrics),
+                Err(err) => {
+                    eprintln!("Failed to serve metrics on {address}, they won't be served: {err}");
+                    None
+                }
+            }
+        });
+
+        let mut monitor = Self {
+            sys,
+            sys_info,
+            networks: sysinfo::Networks::new_with_refreshed_list(),
+            users: sysinfo::Users::new_with_refreshed_list(),
+            diskstats: DiskStatsCollector::new(),
+            gpus,
+            gpu_processes: GpuProcessCollector::new(),
+            disks: sysinfo::Disks::new_with_refreshed_list(),
+            memory: MemoryCollector::new(),
+            cpu_times: CpuTimesCollector::new(),
+            sensors: SensorCollector::new(),
+            metrics,
+            config,
+            last_update: Instant::now(),
+            update_interval: Duration::from_secs(1),
+        };
+        // Some rates, e.g. of Intel GPUs, are only measured from the second refresh on
+        monitor.refresh();
+        monitor
+    }
+
+    /// Samples the system and publishes the sample. Rates are measured since the previous one.
+    pub fn get_stats(&mut self) -> SystemUtilization {
+        let stats = self.refresh();
+        if let Some(metrics) = &self.metrics {
+            metrics.publish(&stats);
+        }
+        stats
+    }
+
+    fn refresh(&mut self) -> SystemUtilization {
+        let update_time = Instant::now();
+        self.update_interval = update_time.duration_since(self.last_update);
+        self.last_update = update_time;
+
+        self.sys.refresh_cpu_specifics(
+            sysinfo::CpuRefreshKind::nothing()
+                .with_cpu_usage()
+                .with_frequency(),
+        );
+        self.sys.refresh_processes_specifics(
+            sysinfo::ProcessesToUpdate::All,
+            true,
+            processes::refresh_kind(),
+        );
+        self.sys.refresh_memory();
+
+        let (cpu_times, core_times) = self.cpu_times.refresh().unwrap_or_default();
+        let cpus = self
+            .sys
+            .cpus()
+            .iter()
+            .map(|cpu| CpuCore {
+                usage: cpu.cpu_usage(),
+                freq: cpu.frequency(),
+                times: procstat::core_index(cpu.name())
+                    .and_then(|index| core_times.get(&index))
+                    .cloned()
+                    .unwrap_or_default(),
+            })
+            .collect();
+        let cpu_usage = self.sys.global_cpu_usage();
+
+        let processes = self.sys.processes().len() as u32;
+        let mem = self.sys.used_memory();
+        let mem_max = self.sys.total_memory();
+        let memory = self.memory.refresh(&self.sys);
+        let up_time = Duration::from_secs(sysinfo::System::uptime());
+
+        let gpus = self.gpus.utilization();
+
+        let disks = self.diskstats.refresh();
+        let disk = if disks.is_empty() {
+            // No `/proc/diskstats`, fall back to what processes report
+            let (read, written) =
+                self.sys
+                    .processes()
+                    .iter()
+                    .fold((0, 0), |(read, write), (_pid, proc)| {
+                        (
+                            read + proc.disk_usage().read_bytes,
+                            write + proc.disk_usage().written_bytes,
+                        )
+                    });
+            Disk {
+                read_bytes: read,
+                writen_bytes: written,
+            }
+        } else {
+            disks
+                .iter()
+                .filter(|disk| !self.diskstats.is_stacked(&disk.name))
+                .fold(Disk::default(), |total, disk| Disk {
+                    read_bytes: total.read_bytes + disk.read_bytes,
+                    writen_bytes: total.writen_bytes + disk.written_bytes,
+                })
+        };
+
+        self.networks.refresh(true);
+        let network = network::network(
+            &self.networks,
+            &self.config.interface_filter,
+            self.update_interval,
+        );
+
+        SystemUtilization {
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_millis() as u64),
+            monotonic: monotonic_millis(),
+            cpus,
+            cpu_usage,
+            cpu_times,
+            mem,
+            processes,
+            mem_max,
+            memory,
+            disk,
+            disks,
+            gpus,
+            up_time,
+            network,
+            sensors: self.sensors.refresh(),
+        }
+    }
+
+    /// Per-process usage as of the most recent [`Self::get_stats`] refresh.
+    pub fn get_processes(&self) -> Vec<ProcessInfo> {
+        processes::process_list(&self.sys, &self.users, self.update_interval)
+    }
+
+    /// Processes using a GPU, with usage since the previous call
+    pub fn get_gpu_processes(&mut self) -> Vec<GpuProcess> {
+        self.gpu_processes.refresh()
+    }
+
+    pub fn get_filesystems(&mut self) -> Vec<Filesystem> {
+        self.disks.refresh(true);
+        filesystems::filesystems(&self.disks, &self.config.filesystem_filter)
+    }
+
+    pub fn signal_process(
+        &mut self,
+        pid: u32,
+        signal: ProcessSignal,
+    ) -> Result<(), ProcessActionError> {
+        processes::send_signal(&mut self.sys, pid, signal)
+    }
+}
+
+/// Milliseconds since boot, including time spent asleep
+#[cfg(target_os = "linux")]
+fn monotonic_millis() -> u64 {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `time` is a valid timespec to write to.
+    // Unlike `CLOCK_MONOTONIC`, used by `Instant`, `CLOCK_BOOTTIME` keeps counting during suspend.
+    unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut time) };
+    time.tv_sec as u64 * 1000 + time.tv_nsec as u64 / 1_000_000
+}
+
+#[cfg(not(target_os = "linux"))]
+fn monotonic_millis() -> u64 {
+    sysinfo::System::uptime() * 1000
+}
@@ -0,0 +1,149 @@
+use shared::{Network, NetworkInterface};
+
+use std::time::Duration;
+
+/// Loopback, container and VM bridges, whose traffic is already counted on a physical interface
+pub const DEFAULT_EXCLUDED_INTERFACES: &[&str] = &[
+    "lo", "docker*", "br-*", "veth*", "virbr*", "vnet*", "cni*", "flannel*",
+];
+
+/// Decides which interfaces count towards the network total.
+///
+/// Patterns are interface names, where `*` matches any sequence of characters and `?` a single one.
+#[derive(Clone, Debug)]
+pub struct InterfaceFilter {
+    /// If not empty, only matching interfaces are included
+    pub include: Vec<String>,
+    /// Matching interfaces are excluded, even if they match `include`
+    pub exclude: Vec<String>,
+}
+
+impl Default for InterfaceFilter {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: DEFAULT_EXCLUDED_INTERFACES
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl InterfaceFilter {
+    pub fn is_included(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| matches_pattern(p, name)))
+            && !self.exclude.iter().any(|p| matches_pattern(p, name))
+    }
+}
+
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+            Some((&p, rest)) => name
+                .split_first()
+                .is_some_and(|(&n, name)| (p == '?' || p == n) && matches(rest, name)),
+        }
+    }
+    let pattern: Vec<_> = pattern.chars().collect();
+    let name: Vec<_> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+/// `interval` is the time elapsed since the previous refresh of `networks`.
+pub fn network(
+    networks: &sysinfo::Networks,
+    filter: &InterfaceFilter,
+    interval: Duration,
+) -> Network {
+    let secs = interval.as_secs_f32().max(f32::EPSILON);
+    let per_sec = |value: u64| (value as f32 / secs) as u64;
+
+    let mut interfaces: Vec<_> = networks
+        .iter()
+        .map(|(name, data)| {
+            let (drops_down, drops_up) = dropped_packets(name);
+            NetworkInterface {
+                name: name.clone(),
+                mac: data.mac_address().to_string(),
+                ips: data.ip_networks().iter().map(|ip| ip.to_string()).collect(),
+                included: filter.is_included(name),
+                down: per_sec(data.received()),
+                up: per_sec(data.transmitted()),
+                packets_down: per_sec(data.packets_received()),
+                packets_up: per_sec(data.packets_transmitted()),
+                errors_down: data.total_errors_on_received(),
+                errors_up: data.total_errors_on_transmitted(),
+                drops_down,
+                drops_up,
+            }
+        })
+        .collect();
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let (down, up) = interfaces
+        .iter()
+        .filter(|interface| interface.included)
+        .fold((0, 0), |(down, up), interface| {
+            (down + interface.down, up + interface.up)
+        });
+    Network {
+        down,
+        up,
+        interfaces,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn dropped_packets(interface: &str) -> (u64, u64) {
+    let read = |counter: &str| {
+        std::fs::read_to_string(format!("/sys/class/net/{interface}/statistics/{counter}"))
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(0)
+    };
+    (read("rx_dropped"), read("tx_dropped"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn dropped_packets(_interface: &str) -> (u64, u64) {
+    (0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_pattern_test() {
+        assert!(matches_pattern("lo", "lo"));
+        assert!(!matches_pattern("lo", "lo0"));
+        assert!(matches_pattern("veth*", "veth1a2b3c"));
+        assert!(matches_pattern("veth*", "veth"));
+        assert!(!matches_pattern("veth*", "eth0"));
+        assert!(matches_pattern("*0", "wlan0"));
+        assert!(matches_pattern("enp?s*", "enp3s0"));
+        assert!(!matches_pattern("enp?s*", "enp10s0"));
+    }
+
+    #[test]
+    fn interface_filter_test() {
+        let filter = InterfaceFilter::default();
+        assert!(filter.is_included("eth0"));
+        assert!(filter.is_included("wlp2s0"));
+        assert!(!filter.is_included("lo"));
+        assert!(!filter.is_included("docker0"));
+        assert!(!filter.is_included("veth1234"));
+
+        let filter = InterfaceFilter {
+            include: vec!["en*".to_owned(), "wl*".to_owned()],
+            exclude: vec!["enx*".to_owned()],
+        };
+        assert!(filter.is_included("enp3s0"));
+        assert!(filter.is_included("wlan0"));
+        assert!(!filter.is_included("enx00e04c680001"));
+        assert!(!filter.is_included("tun0"));
+    }
+}
@@ -0,0 +1,161 @@
+use shared::{ProcessActionError, ProcessInfo, ProcessSignal};
+
+use std::time::Duration;
+
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, Signal, System, UpdateKind, Users};
+
+/// What gets refreshed for every process on each tick.
+///
+/// Like [`System::refresh_processes`], plus the command line and owner of newly spawned processes.
+pub fn refresh_kind() -> ProcessRefreshKind {
+    ProcessRefreshKind::nothing()
+        .with_memory()
+        .with_cpu()
+        .with_disk_usage()
+        .with_exe(UpdateKind::OnlyIfNotSet)
+        .with_cmd(UpdateKind::OnlyIfNotSet)
+        .with_user(UpdateKind::OnlyIfNotSet)
+        .with_tasks()
+}
+
+/// `interval` is the time elapsed between the last two process refreshes.
+pub fn process_list(sys: &System, users: &Users, interval: Duration) -> Vec<ProcessInfo> {
+    let cpu_count = sys.cpus().len().max(1) as f32;
+    let secs = interval.as_secs_f32().max(f32::EPSILON);
+
+    sys.processes()
+        .values()
+        // On Linux threads are listed alongside processes
+        .filter(|proc| proc.thread_kind().is_none())
+        .map(|proc| {
+            let user = proc
+                .user_id()
+                .and_then(|uid| users.get_user_by_id(uid))
+                .map_or(String::new(), |user| user.name().to_owned());
+            let cmd = proc
+                .cmd()
+                .iter()
+                .map(|arg| arg.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let disk_usage = proc.disk_usage();
+
+            ProcessInfo {
+                pid: proc.pid().as_u32(),
+                parent: proc.parent().map(|pid| pid.as_u32()),
+                name: proc.name().to_string_lossy().into_owned(),
+                user,
+                cmd,
+                cpu_usage: proc.cpu_usage() / cpu_count,
+                mem: proc.memory(),
+                disk_read: (disk_usage.read_bytes as f32 / secs) as u64,
+                disk_write: (disk_usage.written_bytes as f32 / secs) as u64,
+                state: proc.status().to_string(),
+            }
+        })
+        .collect()
+}
+
+fn to_sysinfo_signal(signal: ProcessSignal) -> Signal {
+    match signal {
+        ProcessSignal::Terminate => Signal::Term,
+        ProcessSignal::Kill => Signal::Kill,
+        ProcessSignal::Suspend => Signal::Stop,
+        ProcessSignal::Resume => Signal::Continue,
+    }
+}
+
+/// Returns `true` if the process is (still) alive. Doesn't touch CPU and disk usage counters.
+fn refresh_process(sys: &mut System, pid: Pid) -> bool {
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing(),
+    );
+    sys.process(pid).is_some()
+}
+
+pub fn send_signal(
+    sys: &mut System,
+    pid: u32,
+    signal: ProcessSignal,
+) -> Result<(), ProcessActionError> {
+    let pid = Pid::from_u32(pid);
+    refresh_process(sys, pid);
+    let process = sys.process(pid).ok_or(ProcessActionError::NotFound)?;
+
+    match process.kill_with(to_sysinfo_signal(signal)) {
+        None => Err(ProcessActionError::Unsupported),
+        Some(true) => Ok(()),
+        // `kill` only fails for invalid signals, missing permissions or a missing process
+        Some(false) if refresh_process(sys, pid) => Err(ProcessActionError::PermissionDenied),
+        Some(false) => Err(ProcessActionError::NotFound),
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    use std::{
+        os::unix::process::ExitStatusExt,
+        process::Command,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use sysinfo::ProcessStatus;
+
+    fn wait_for_status(sys: &mut System, pid: u32, status: ProcessStatus) -> bool {
+        let pid = Pid::from_u32(pid);
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while Instant::now() < deadline {
+            refresh_process(sys, pid);
+            if sys.process(pid).map(|proc| proc.status()) == Some(status) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn signal_state_transitions() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+        let mut sys = System::new();
+
+        send_signal(&mut sys, pid, ProcessSignal::Suspend).unwrap();
+        assert!(wait_for_status(&mut sys, pid, ProcessStatus::Stop));
+
+        send_signal(&mut sys, pid, ProcessSignal::Resume).unwrap();
+        assert!(wait_for_status(&mut sys, pid, ProcessStatus::Sleep));
+
+        send_signal(&mut sys, pid, ProcessSignal::Kill).unwrap();
+        let exit_status = child.wait().unwrap();
+        assert_eq!(Some(9), exit_status.signal());
+
+        assert_eq!(
+            Err(ProcessActionError::NotFound),
+            send_signal(&mut sys, pid, ProcessSignal::Terminate)
+        );
+    }
+
+    #[test]
+    fn signal_terminate() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let mut sys = System::new();
+
+        send_signal(&mut sys, child.id(), ProcessSignal::Terminate).unwrap();
+        assert_eq!(Some(15), child.wait().unwrap().signal());
+    }
+
+    #[test]
+    fn signal_missing_process() {
+        let mut sys = System::new();
+        assert_eq!(
+            Err(ProcessActionError::NotFound),
+            send_signal(&mut sys, i32::MAX as u32, ProcessSignal::Kill)
+        );
+    }
+}
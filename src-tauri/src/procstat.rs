@@ -0,0 +1,222 @@
+use shared::CpuTimes;
+
+use std::{collections::BTreeMap, fs};
+
+/// Cumulative time spent in each state, in clock ticks
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CpuTicks {
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub steal: u64,
+}
+
+impl CpuTicks {
+    fn total(&self) -> u64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+
+    /// Share of each state between `prev` and `self` in percent
+    pub fn times_since(&self, prev: &CpuTicks) -> CpuTimes {
+        // Counters go back when a core is hot-plugged
+        let delta = |prev: u64, curr: u64| curr.saturating_sub(prev);
+        let total = delta(prev.total(), self.total());
+        let percent = |prev: u64, curr: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                delta(prev, curr) as f32 / total as f32 * 100.0
+            }
+        };
+
+        CpuTimes {
+            user: percent(prev.user, self.user),
+            nice: percent(prev.nice, self.nice),
+            system: percent(prev.system, self.system),
+            idle: percent(prev.idle, self.idle),
+            iowait: percent(prev.iowait, self.iowait),
+            irq: percent(prev.irq, self.irq),
+            softirq: percent(prev.softirq, self.softirq),
+            steal: percent(prev.steal, self.steal),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProcStat {
+    /// Sum over all cores
+    pub total: CpuTicks,
+    /// Online cores by their number, which skips offline ones
+    pub cores: BTreeMap<usize, CpuTicks>,
+}
+
+/// The number of a core named as in `/proc/stat`, e.g. 3 for `cpu3`. sysinfo names cores the same.
+pub fn core_index(name: &str) -> Option<usize> {
+    name.strip_prefix("cpu")?.parse().ok()
+}
+
+/// See <https://www.kernel.org/doc/Documentation/filesystems/proc.rst>, section 1.8
+pub fn parse_proc_stat(text: &str) -> Option<ProcStat> {
+    let mut total = None;
+    let mut cores = BTreeMap::new();
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        let Some(name) = fields.next().filter(|name| name.starts_with("cpu")) else {
+            continue;
+        };
+        let values = fields
+            .map(|field| field.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        if values.len() < 4 {
+            return None;
+        }
+        // Fields were added over time. `guest` and `guest_nice` are already in `user` and `nice`.
+        let value = |i: usize| values.get(i).copied().unwrap_or(0);
+        let ticks = CpuTicks {
+            user: value(0),
+            nice: value(1),
+            system: value(2),
+            idle: value(3),
+            iowait: value(4),
+            irq: value(5),
+            softirq: value(6),
+            steal: value(7),
+        };
+        if name == "cpu" {
+            total = Some(ticks);
+        } else if let Some(index) = core_index(name) {
+            cores.insert(index, ticks);
+        }
+    }
+    Some(ProcStat {
+        total: total?,
+        cores,
+    })
+}
+
+pub struct CpuTimesCollector {
+    previous: Option<ProcStat>,
+}
+
+impl CpuTimesCollector {
+    pub fn new() -> Self {
+        Self {
+            previous: read_proc_stat(),
+        }
+    }
+
+    /// Aggregate times since the previous call and those of every core online for both calls, by
+    /// the core's number. `None` if `/proc/stat` isn't available.
+    pub fn refresh(&mut self) -> Option<(CpuTimes, BTreeMap<usize, CpuTimes>)> {
+        let current = read_proc_stat();
+        let times = match (&self.previous, &current) {
+            (Some(prev), Some(curr)) => Some((
+                curr.total.times_since(&prev.total),
+                curr.cores
+                    .iter()
+                    .filter_map(|(index, curr)| {
+                        Some((*index, curr.times_since(prev.cores.get(index)?)))
+                    })
+                    .collect(),
+            )),
+            _ => None,
+        };
+        self.previous = current;
+        times
+    }
+}
+
+fn read_proc_stat() -> Option<ProcStat> {
+    parse_proc_stat(&fs::read_to_string("/proc/stat").ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROC_STAT: &str = "\
+cpu  10132153 290696 3084719 46828483 16683 0 25195 175 0 0
+cpu0 1393280 32966 572056 13343292 6130 0 17875 100 0 0
+cpu1 1335212 31634 442765 13382145 3563 0 7320 75 0 0
+intr 1462898 27 2 0 0 0 0 0 0 1 0 0 0 12 0 0
+ctxt 115315
+btime 1769097600
+processes 2915
+procs_running 1
+";
+
+    #[test]
+    fn parse_proc_stat_test() {
+        let stat = parse_proc_stat(PROC_STAT).unwrap();
+        assert_eq!(
+            CpuTicks {
+                user: 10132153,
+                nice: 290696,
+                system: 3084719,
+                idle: 46828483,
+                iowait: 16683,
+                irq: 0,
+                softirq: 25195,
+                steal: 175,
+            },
+            stat.total
+        );
+        assert_eq!(2, stat.cores.len());
+        assert_eq!(7320, stat.cores[&1].softirq);
+    }
+
+    #[test]
+    fn parse_proc_stat_offline_core() {
+        // cpu1 is offline
+        let stat = parse_proc_stat("cpu 2 2 2 2\ncpu0 1 1 1 1\ncpu2 1 1 1 1\n").unwrap();
+        assert_eq!(vec![0, 2], stat.cores.keys().copied().collect::<Vec<_>>());
+        assert_eq!(Some(2), core_index("cpu2"));
+        assert_eq!(None, core_index("cpu"));
+    }
+
+    #[test]
+    fn parse_proc_stat_old_kernel() {
+        // Before Linux 2.6 only the first four fields existed
+        let stat = parse_proc_stat("cpu 1 2 3 4\ncpu0 1 2 3 4\n").unwrap();
+        assert_eq!(4, stat.total.idle);
+        assert_eq!(0, stat.total.steal);
+    }
+
+    #[test]
+    fn parse_proc_stat_malformed() {
+        assert_eq!(None, parse_proc_stat(""));
+        assert_eq!(None, parse_proc_stat("cpu 1 2\n"));
+        assert_eq!(None, parse_proc_stat("cpu 1 2 x 4\n"));
+    }
+
+    #[test]
+    fn times_since_test() {
+        let prev = CpuTicks::default();
+        let curr = CpuTicks {
+            user: 50,
+            system: 20,
+            idle: 20,
+            irq: 5,
+            steal: 5,
+            ..Default::default()
+        };
+        let times = curr.times_since(&prev);
+        assert_eq!(50.0, times.user);
+        assert_eq!(20.0, times.idle);
+        assert_eq!(25.0, times.kernel());
+        assert_eq!(5.0, times.steal);
+
+        assert_eq!(0.0, prev.times_since(&prev).user);
+    }
+}
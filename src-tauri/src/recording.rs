@@ -0,0 +1,158 @@
+use shared::{SAMPLE_INTERVAL, SystemUtilization};
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    history::History,
+    monitor::SystemMonitor,
+    sampler,
+    store::{HEADER_LEN, VERSION, decode_records, encode_record},
+};
+
+/// Start of a recording, followed by the store's [`VERSION`]
+const MAGIC: &[u8; 4] = b"RMRC";
+
+/// Writes samples to a file as they're taken, framed like the records of a
+/// [`Store`](crate::store::Store), so that a recorder that gets killed loses at most one sample
+pub struct Recorder {
+    file: File,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        let mut header = MAGIC.to_vec();
+        header.extend(VERSION.to_le_bytes());
+        file.write_all(&header)?;
+        Ok(Self { file })
+    }
+
+    pub fn append(&mut self, sample: &SystemUtilization) -> io::Result<()> {
+        self.file.write_all(&encode_record(sample))
+    }
+}
+
+/// Samples the system into a new recording at `path` until `duration` is up, or forever
+pub fn record(
+    monitor: &mut SystemMonitor,
+    path: &Path,
+    duration: Option<Duration>,
+) -> io::Result<()> {
+    let mut recorder = Recorder::create(path)?;
+    let end = duration.map(|duration| Instant::now() + duration);
+    let mut result = Ok(());
+    sampler::run(SAMPLE_INTERVAL, || {
+        result = recorder.append(&monitor.get_stats());
+        result.is_ok() && end.is_none_or(|end| Instant::now() < end)
+    });
+    result
+}
+
+/// Reads the samples of a recording, oldest first, up to the first damaged one
+pub fn read(path: &Path) -> io::Result<Vec<SystemUtilization>> {
+    let data = fs::read(path)?;
+    let header = data.get(..HEADER_LEN);
+    if header.is_none_or(|header| header[..MAGIC.len()] != *MAGIC) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a recording",
+        ));
+    }
+    if data[MAGIC.len()..HEADER_LEN] != VERSION.to_le_bytes() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "recorded by another version",
+        ));
+    }
+    let (samples, _) = decode_records(&data[HEADER_LEN..]);
+    Ok(samples)
+}
+
+/// The recording being replayed instead of monitoring the system, if any
+pub struct ReplayState(Option<Vec<SystemUtilization>>);
+
+impl ReplayState {
+    pub fn new(samples: Option<Vec<SystemUtilization>>) -> Self {
+        Self(samples)
+    }
+
+    /// Whether a recording is being replayed
+    pub fn is_replaying(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// A history of the whole recording, with every sample at full resolution
+    pub fn history(&self) -> History {
+        let samples = self.0.as_deref().unwrap_or_default();
+        let mut history = History::with_capacity(samples.len().max(1));
+        for sample in samples {
+            history.push(sample.clone());
+        }
+        history
+    }
+
+    /// When the first and the last sample were taken, `None` if there are no samples
+    pub fn range(&self) -> Option<(u64, u64)> {
+        let samples = self.0.as_ref()?;
+        Some((samples.first()?.timestamp, samples.last()?.timestamp))
+    }
+
+    /// Samples taken after `from` and up to `to`
+    pub fn samples(&self, from: u64, to: u64) -> &[SystemUtilization] {
+        let samples = self.0.as_deref().unwrap_or_default();
+        let start = samples.partition_point(|sample| sample.timestamp <= from);
+        let end = samples.partition_point(|sample| sample.timestamp <= to);
+        &samples[start..end.max(start)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::{sample, timestamps};
+
+    #[test]
+    fn read_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.rec");
+        let mut recorder = Recorder::create(&path).unwrap();
+        for timestamp in [1000, 2000, 3000] {
+            recorder.append(&sample(timestamp)).unwrap();
+        }
+        drop(recorder);
+        let samples = read(&path).unwrap();
+        assert_eq!(vec![1000, 2000, 3000], timestamps(&samples));
+        assert_eq!(2000, samples[1].disks[0].read_bytes);
+
+        // Killed while writing the last sample
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        assert_eq!(vec![1000, 2000], timestamps(&read(&path).unwrap()));
+
+        fs::write(&path, b"RMHS").unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, read(&path).unwrap_err().kind());
+    }
+
+    #[test]
+    fn replay_test() {
+        let replay = ReplayState::new(Some((1..=5).map(|i| sample(i * 1000)).collect()));
+        assert!(replay.is_replaying());
+        assert_eq!(Some((1000, 5000)), replay.range());
+        assert_eq!(vec![2000, 3000], timestamps(replay.samples(1000, 3000)));
+        assert!(replay.samples(6000, 7000).is_empty());
+        assert!(replay.samples(3000, 1000).is_empty());
+        // Samples from the start of the recording are kept at full resolution
+        assert_eq!(5, replay.history().range(0, 5000, 1000).len());
+
+        let live = ReplayState::new(None);
+        assert!(!live.is_replaying());
+        assert_eq!(None, live.range());
+        assert!(live.samples(0, u64::MAX).is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+use shared::{SAMPLE_INTERVAL, STATS_EVENT};
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::{history::HistoryState, monitor::SystemMonitorState, store::HistoryStore};
+
+/// Samples the system on a background thread, records every sample in the [`HistoryState`]
+/// and the `store`, if any, and emits it as a [`STATS_EVENT`].
+///
+/// Sampling doesn't depend on the webview, so it keeps going while the window is hidden.
+pub fn spawn(app: AppHandle, mut store: Option<HistoryStore>) {
+    thread::spawn(move || {
+        run(SAMPLE_INTERVAL, || {
+            let stats = app
+                .state::<SystemMonitorState>()
+                .get_state()
+                .unwrap()
+                .get_stats();
+            let history_state = app.state::<HistoryState>();
+            let mut history = history_state.get_state().unwrap();
+            let rollups = history.push(stats.clone());
+            // Saved before the history is queried again, as it reads the rollups from the store
+            if let Some(Err(err)) = store.as_mut().map(|store| store.append(&stats, &rollups)) {
+                eprintln!("Failed to save history, no longer saving it: {err}");
+                store = None;
+                history.stop_saving();
+            }
+            drop(history);
+            // Nothing to do if the window is gone, the next sample may have a listener again
+            let _ = app.emit(STATS_EVENT, stats);
+            true
+        });
+    });
+}
+
+/// Calls `sample` every `interval` for as long as it returns `true`, starting an `interval` from
+/// now so that the rates of the first sample are measured over a whole one
+pub fn run(interval: Duration, mut sample: impl FnMut() -> bool) {
+    let mut next = Instant::now();
+    loop {
+        next += interval;
+        let now = Instant::now();
+        if next < now {
+            // Fell behind, e.g. after a suspend, so start over instead of catching up
+            next = now;
+        }
+        thread::sleep(next - now);
+        if !sample() {
+            break;
+        }
+    }
+}
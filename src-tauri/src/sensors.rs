@@ -0,0 +1,181 @@
+use shared::Sensor;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub struct SensorCollector {
+    hwmon: PathBuf,
+    /// Only used when hwmon has no temperature sensors, i.e. outside of Linux
+    components: Option<sysinfo::Components>,
+}
+
+impl SensorCollector {
+    pub fn new() -> Self {
+        Self::with_hwmon_path("/sys/class/hwmon")
+    }
+
+    pub fn with_hwmon_path(hwmon: impl Into<PathBuf>) -> Self {
+        Self {
+            hwmon: hwmon.into(),
+            components: None,
+        }
+    }
+
+    pub fn refresh(&mut self) -> Vec<Sensor> {
+        let sensors = read_hwmon(&self.hwmon);
+        if !sensors.is_empty() {
+            return sensors;
+        }
+
+        let components = self
+            .components
+            .get_or_insert_with(sysinfo::Components::new_with_refreshed_list);
+        components.refresh(false);
+        components
+            .iter()
+            .filter_map(|component| {
+                Some(Sensor {
+                    chip: String::new(),
+                    device: String::new(),
+                    label: component.label().to_owned(),
+                    temp: component.temperature()?,
+                    max: component.max(),
+                    critical: component.critical(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Reads a value in millidegrees Celsius
+fn read_millidegrees(path: &Path) -> Option<f32> {
+    let value: i64 = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+    Some(value as f32 / 1000.0)
+}
+
+/// Splits `temp3_input` into `3`. Other sensor types (fans, voltages) are skipped.
+fn temp_index(file_name: &str) -> Option<u32> {
+    file_name
+        .strip_prefix("temp")?
+        .strip_suffix("_input")?
+        .parse()
+        .ok()
+}
+
+/// See <https://www.kernel.org/doc/Documentation/hwmon/sysfs-interface.rst>
+fn read_hwmon(hwmon: &Path) -> Vec<Sensor> {
+    let Ok(entries) = fs::read_dir(hwmon) else {
+        return Vec::new();
+    };
+    let mut chips: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    // So that `hwmon10` comes after `hwmon9`
+    chips.sort_by_key(|path| (path.as_os_str().len(), path.clone()));
+
+    let mut sensors = Vec::new();
+    for chip_dir in chips {
+        let chip = fs::read_to_string(chip_dir.join("name"))
+            .map(|name| name.trim().to_owned())
+            .unwrap_or_default();
+        // Named after what the chip is attached to, so that two identical drives differ.
+        // The directory itself when it isn't attached to anything.
+        let device = fs::read_link(chip_dir.join("device"))
+            .unwrap_or_else(|_| chip_dir.clone())
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+
+        let Ok(files) = fs::read_dir(&chip_dir) else {
+            continue;
+        };
+        let mut indices: Vec<_> = files
+            .flatten()
+            .filter_map(|file| temp_index(&file.file_name().to_string_lossy()))
+            .collect();
+        indices.sort_unstable();
+
+        for index in indices {
+            let file = |suffix: &str| chip_dir.join(format!("temp{index}_{suffix}"));
+            // Reading fails e.g. for sensors of a device that's powered down
+            let Some(temp) = read_millidegrees(&file("input")) else {
+                continue;
+            };
+            let label = fs::read_to_string(file("label"))
+                .map(|label| label.trim().to_owned())
+                .unwrap_or_else(|_| format!("temp{index}"));
+            sensors.push(Sensor {
+                chip: chip.clone(),
+                device: device.clone(),
+                label,
+                temp,
+                max: read_millidegrees(&file("max")),
+                critical: read_millidegrees(&file("crit")),
+            });
+        }
+    }
+    sensors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/hwmon")
+    }
+
+    #[test]
+    fn read_hwmon_test() {
+        let sensors = read_hwmon(&fixture());
+        let labels: Vec<_> = sensors
+            .iter()
+            .map(|sensor| format!("{} {}", sensor.chip, sensor.label))
+            .collect();
+        assert_eq!(
+            vec![
+                "coretemp Package id 0",
+                "coretemp Core 0",
+                "nvme Composite",
+                "acpitz temp1",
+                "nvme Composite"
+            ],
+            labels
+        );
+        let devices: Vec<_> = sensors
+            .iter()
+            .map(|sensor| sensor.device.as_str())
+            .collect();
+        assert_eq!(
+            vec!["hwmon0", "hwmon0", "nvme0", "hwmon2", "nvme1"],
+            devices
+        );
+
+        assert_eq!(45.0, sensors[0].temp);
+        assert_eq!(Some(80.0), sensors[0].max);
+        assert_eq!(Some(100.0), sensors[0].critical);
+        assert_eq!(38.85, sensors[2].temp);
+        assert_eq!(Some(84.85), sensors[2].critical);
+        assert_eq!(None, sensors[3].max);
+        assert_eq!(None, sensors[3].critical);
+    }
+
+    #[test]
+    fn cpu_package_test() {
+        let mut collector = SensorCollector::with_hwmon_path(fixture());
+        let sensors = collector.refresh();
+        let package: Vec<_> = sensors
+            .iter()
+            .filter(|sensor| sensor.is_cpu_package())
+            .map(|sensor| sensor.label.as_str())
+            .collect();
+        assert_eq!(vec!["Package id 0"], package);
+    }
+
+    #[test]
+    fn temp_index_test() {
+        assert_eq!(Some(1), temp_index("temp1_input"));
+        assert_eq!(Some(12), temp_index("temp12_input"));
+        assert_eq!(None, temp_index("temp1_label"));
+        assert_eq!(None, temp_index("in0_input"));
+    }
+}
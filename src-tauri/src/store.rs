@@ -0,0 +1,543 @@
+use shared::{Rollup, SAMPLE_INTERVAL, SystemUtilization};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    iter,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::history::{History, RAW_LENGTH};
+
+/// Start of every segment file, followed by [`VERSION`]
+const MAGIC: &[u8; 4] = b"RMHS";
+/// Bumped whenever the encoding of a [`Record`] changes. Segments of other versions are ignored.
+pub(crate) const VERSION: u32 = 5;
+pub(crate) const HEADER_LEN: usize = MAGIC.len() + 4;
+/// Length and CRC-32 of the payload, both little endian
+const RECORD_HEADER_LEN: usize = 8;
+/// Number of segments a [`HistoryStore`] splits the length of a tier into. Retention deletes whole
+/// segments.
+const SEGMENTS_PER_TIER: u32 = 24;
+const SEGMENT_EXTENSION: &str = "seg";
+
+#[derive(Clone, Debug)]
+pub struct StoreConfig {
+    pub dir: PathBuf,
+    /// Records older than this are deleted
+    pub max_age: Duration,
+    /// In bytes, the oldest records are deleted once the store grows larger
+    pub max_size: u64,
+}
+
+impl StoreConfig {
+    /// `$XDG_DATA_HOME/resource-monitor/history` on Linux
+    pub fn default_dir() -> Option<PathBuf> {
+        Some(
+            dirs::data_dir()?
+                .join(env!("CARGO_PKG_NAME"))
+                .join("history"),
+        )
+    }
+}
+
+/// What a [`Store`] holds
+pub trait Record: Serialize + DeserializeOwned {
+    /// In milliseconds since the Unix epoch
+    fn timestamp(&self) -> u64;
+}
+
+impl Record for SystemUtilization {
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
+
+impl Record for Rollup {
+    fn timestamp(&self) -> u64 {
+        self.avg.timestamp
+    }
+}
+
+/// A segment file, named after the timestamp of its first record, see [`Store::create_segment`]
+struct Segment {
+    start: u64,
+    path: PathBuf,
+}
+
+/// Segments in the directory, oldest first
+fn segments(dir: &Path) -> io::Result<Vec<Segment>> {
+    let mut segments: Vec<_> = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == SEGMENT_EXTENSION))
+        .filter_map(|path| {
+            let start = path.file_stem()?.to_str()?.parse().ok()?;
+            Some(Segment { start, path })
+        })
+        .collect();
+    segments.sort_by_key(|segment| segment.start);
+    Ok(segments)
+}
+
+pub(crate) fn encode_record(record: &impl Record) -> Vec<u8> {
+    // Only fails for types serde can't represent, which records don't contain
+    let payload = postcard::to_stdvec(record).unwrap();
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+    record.extend((payload.len() as u32).to_le_bytes());
+    record.extend(crc32fast::hash(&payload).to_le_bytes());
+    record.extend(payload);
+    record
+}
+
+/// Records up to the first incomplete or damaged one, and the number of bytes they take
+pub(crate) fn decode_records<T: Record>(data: &[u8]) -> (Vec<T>, usize) {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = data.get(offset..offset + RECORD_HEADER_LEN) {
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+        let start = offset + RECORD_HEADER_LEN;
+        let Some(payload) = data.get(start..start + len) else {
+            break;
+        };
+        if crc32fast::hash(payload) != crc {
+            break;
+        }
+        let Ok(record) = postcard::from_bytes(payload) else {
+            break;
+        };
+        records.push(record);
+        offset = start + len;
+    }
+    (records, offset)
+}
+
+/// Reads a segment up to its first incomplete or damaged record. If `truncate`, whatever follows
+/// the last good record, e.g. a record cut short by a crash, is cut off, which only the process
+/// writing the segment may do: to others, it may be a record being written.
+fn read_segment<T: Record>(path: &Path, truncate: bool) -> io::Result<Vec<T>> {
+    let data = fs::read(path)?;
+    let Some(header) = data.get(..HEADER_LEN) else {
+        return Ok(Vec::new());
+    };
+    if &header[..MAGIC.len()] != MAGIC
+        || u32::from_le_bytes(header[MAGIC.len()..].try_into().unwrap()) != VERSION
+    {
+        return Ok(Vec::new());
+    }
+
+    let (records, len) = decode_records(&data[HEADER_LEN..]);
+    if truncate && HEADER_LEN + len < data.len() {
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len((HEADER_LEN + len) as u64)?;
+    }
+    Ok(records)
+}
+
+/// Records in the segments of `dir` taken after `since`, oldest first, see [`read_segment`]
+fn read_records<T: Record>(dir: &Path, since: u64, truncate: bool) -> io::Result<Vec<T>> {
+    let segments = segments(dir)?;
+    let mut records = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        // Every record of a segment was taken before the next one started
+        if segments.get(i + 1).is_some_and(|next| next.start <= since) {
+            continue;
+        }
+        let segment_records = read_segment::<T>(&segment.path, truncate)?;
+        records.extend(
+            segment_records
+                .into_iter()
+                .filter(|record| record.timestamp() > since),
+        );
+    }
+    Ok(records)
+}
+
+/// Append-only on-disk records, split into segments of `segment_length`.
+///
+/// Every record is framed by its length and checksum, so that a crash while writing loses at most
+/// the record being written.
+pub struct Store<T> {
+    config: StoreConfig,
+    segment_length: Duration,
+    /// Start of the segment being written and its file
+    current: Option<(u64, File)>,
+    records: PhantomData<T>,
+}
+
+impl<T: Record> Store<T> {
+    /// Opens the store and returns the records taken after `since`, in milliseconds since the Unix
+    /// epoch.
+    pub fn open(
+        config: StoreConfig,
+        segment_length: Duration,
+        since: u64,
+    ) -> io::Result<(Self, Vec<T>)> {
+        fs::create_dir_all(&config.dir)?;
+        let records = read_records(&config.dir, since, true)?;
+        let store = Self {
+            config,
+            segment_length,
+            current: None,
+            records: PhantomData,
+        };
+        Ok((store, records))
+    }
+
+    /// Reads the records taken after `since` without opening the store, so that it's safe to do
+    /// while another process writes to it. A store that doesn't exist yet holds no records.
+    pub fn read(config: &StoreConfig, since: u64) -> io::Result<Vec<T>> {
+        match read_records(&config.dir, since, false) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            result => result,
+        }
+    }
+
+    /// Writes a record, starting a new segment and deleting old ones once the current segment is
+    /// full
+    pub fn append(&mut self, record: &T) -> io::Result<()> {
+        let timestamp = record.timestamp();
+        let segment_full = self.current.as_ref().is_none_or(|(start, _)| {
+            timestamp.saturating_sub(*start) >= self.segment_length.as_millis() as u64
+        });
+        if segment_full {
+            let (start, mut file) = self.create_segment(timestamp)?;
+            let mut header = MAGIC.to_vec();
+            header.extend(VERSION.to_le_bytes());
+            file.write_all(&header)?;
+            self.current = Some((start, file));
+            self.apply_retention(timestamp)?;
+        }
+
+        let (_, file) = self.current.as_mut().unwrap();
+        // A single write, so that a crash leaves at most one incomplete record
+        file.write_all(&encode_record(record))
+    }
+
+    /// Creates a segment for records from `timestamp` on, named after the next free millisecond if
+    /// a segment already starts then, e.g. because the clock was set back
+    fn create_segment(&self, timestamp: u64) -> io::Result<(u64, File)> {
+        let mut start = timestamp;
+        loop {
+            let path = self.config.dir.join(format!("{start}.{SEGMENT_EXTENSION}"));
+            match OpenOptions::new().append(true).create_new(true).open(path) {
+                Ok(file) => return Ok((start, file)),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => start += 1,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Deletes segments that only hold expired records, then the oldest ones while the store is too
+    /// large
+    fn apply_retention(&self, now: u64) -> io::Result<()> {
+        let cutoff = now.saturating_sub(self.config.max_age.as_millis() as u64);
+        let current = self.current.as_ref().map(|(start, _)| *start);
+        let segments: Vec<_> = segments(&self.config.dir)?
+            .into_iter()
+            .filter(|segment| Some(segment.start) != current)
+            .collect();
+        let sizes: Vec<_> = segments
+            .iter()
+            .map(|segment| fs::metadata(&segment.path).map_or(0, |metadata| metadata.len()))
+            .collect();
+        let mut total_size: u64 = sizes.iter().sum();
+
+        for (i, segment) in segments.iter().enumerate() {
+            let end = segments.get(i + 1).map_or(current, |next| Some(next.start));
+            let expired = end.is_some_and(|end| end <= cutoff);
+            if !expired && total_size <= self.config.max_size {
+                break;
+            }
+            fs::remove_file(&segment.path)?;
+            total_size -= sizes[i];
+        }
+        Ok(())
+    }
+}
+
+/// A [`Store`] for the samples at full resolution and one for every tier of rollups,
+/// in subdirectories named after their resolution
+pub struct HistoryStore {
+    samples: Store<SystemUtilization>,
+    tiers: Vec<Store<Rollup>>,
+}
+
+impl HistoryStore {
+    /// The config of the samples' store followed by those of the tiers, along with their segment
+    /// length and since when their records are restored
+    fn stores(
+        config: &StoreConfig,
+        history: &History,
+        now: u64,
+    ) -> Vec<(StoreConfig, Duration, u64)> {
+        let max_size = config.max_size / (history.tiers().len() as u64 + 1);
+        let store = |resolution: Duration, length: Duration| {
+            let config = StoreConfig {
+                dir: config.dir.join(format!("{}s", resolution.as_secs())),
+                max_age: config.max_age.min(length),
+                max_size,
+            };
+            let since = now.saturating_sub(length.as_millis() as u64);
+            (config, length / SEGMENTS_PER_TIER, since)
+        };
+        let tiers = history.tiers().iter();
+        iter::once(store(SAMPLE_INTERVAL, RAW_LENGTH))
+            .chain(tiers.map(|tier| store(tier.resolution, tier.length)))
+            .collect()
+    }
+
+    /// Opens the stores and loads the samples they hold into `history`, which reads the rollups
+    /// from them when it needs to, see [`History::save_to`].
+    ///
+    /// The size limit is split evenly between the stores.
+    pub fn open(config: StoreConfig, history: &mut History, now: u64) -> io::Result<Self> {
+        let mut stores = Self::stores(&config, history, now).into_iter();
+        let (sample_config, segment_length, sample_since) = stores.next().unwrap();
+        let (samples, restored_samples) = Store::open(sample_config, segment_length, sample_since)?;
+        let resolutions: Vec<_> = history.tiers().iter().map(|tier| tier.resolution).collect();
+        let mut tiers = Vec::new();
+        let mut tier_configs = Vec::new();
+        let mut restored_rollups = Vec::new();
+        for ((tier_config, segment_length, _), resolution) in stores.zip(resolutions) {
+            // Only the buckets the restored samples may be in
+            let since = sample_since.saturating_sub(resolution.as_millis() as u64);
+            let (store, rollups) = Store::open(tier_config.clone(), segment_length, since)?;
+            tiers.push(store);
+            tier_configs.push(tier_config);
+            restored_rollups.push(rollups);
+        }
+        history.save_to(tier_configs);
+        history.restore(restored_samples, restored_rollups);
+
+        Ok(Self { samples, tiers })
+    }
+
+    /// Loads what the stores hold into `history` without opening them, see [`Store::read`]
+    pub fn read(config: &StoreConfig, history: &mut History, now: u64) -> io::Result<()> {
+        let mut stores = Self::stores(config, history, now).into_iter();
+        let (sample_config, _, since) = stores.next().unwrap();
+        let samples = Store::read(&sample_config, since)?;
+        let rollups = stores
+            .map(|(tier_config, _, since)| Store::read(&tier_config, since))
+            .collect::<io::Result<_>>()?;
+        history.restore(samples, rollups);
+        Ok(())
+    }
+
+    /// Writes a sample and the rollups it completed, as returned by [`History::push`]
+    pub fn append(
+        &mut self,
+        sample: &SystemUtilization,
+        rollups: &[(usize, Rollup)],
+    ) -> io::Result<()> {
+        self.samples.append(sample)?;
+        for (tier, rollup) in rollups {
+            self.tiers[*tier].append(rollup)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::{sample, timestamps};
+
+    const HOUR: u64 = 60 * 60 * 1000;
+
+    fn config(dir: &Path) -> StoreConfig {
+        StoreConfig {
+            dir: dir.to_owned(),
+            max_age: Duration::from_secs(24 * 60 * 60),
+            max_size: u64::MAX,
+        }
+    }
+
+    fn open(config: StoreConfig, since: u64) -> (Store<SystemUtilization>, Vec<SystemUtilization>) {
+        Store::open(config, Duration::from_secs(60 * 60), since).unwrap()
+    }
+
+    #[test]
+    fn reload_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut store, samples) = open(config(dir.path()), 0);
+        assert!(samples.is_empty());
+        for timestamp in [1000, 2000, HOUR + 1000, HOUR + 2000] {
+            store.append(&sample(timestamp)).unwrap();
+        }
+        drop(store);
+
+        assert_eq!(2, segments(dir.path()).unwrap().len());
+        let (_, samples) = open(config(dir.path()), 0);
+        assert_eq!(
+            vec![1000, 2000, HOUR + 1000, HOUR + 2000],
+            timestamps(&samples)
+        );
+        assert_eq!(HOUR + 1000, samples[2].disks[0].read_bytes);
+
+        let (_, samples) = open(config(dir.path()), 2000);
+        assert_eq!(vec![HOUR + 1000, HOUR + 2000], timestamps(&samples));
+    }
+
+    #[test]
+    fn clock_set_back_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut store, _) = open(config(dir.path()), 0);
+        store.append(&sample(1000)).unwrap();
+        drop(store);
+
+        // Restarted after the clock was set back to when the first segment started
+        let (mut store, _) = open(config(dir.path()), 0);
+        store.append(&sample(1000)).unwrap();
+        store.append(&sample(2000)).unwrap();
+        drop(store);
+        let starts: Vec<_> = segments(dir.path())
+            .unwrap()
+            .iter()
+            .map(|segment| segment.start)
+            .collect();
+        assert_eq!(vec![1000, 1001], starts);
+        let (_, samples) = open(config(dir.path()), 0);
+        assert_eq!(vec![1000, 1000, 2000], timestamps(&samples));
+    }
+
+    #[test]
+    fn truncated_record_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut store, _) = open(config(dir.path()), 0);
+        store.append(&sample(1000)).unwrap();
+        store.append(&sample(2000)).unwrap();
+        drop(store);
+
+        // A crash in the middle of writing the second record
+        let path = &segments(dir.path()).unwrap()[0].path;
+        let len = fs::metadata(path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+
+        let (mut store, samples) = open(config(dir.path()), 0);
+        assert_eq!(vec![1000], timestamps(&samples));
+        // The damaged record is gone, so records written later are readable again
+        store.append(&sample(3000)).unwrap();
+        drop(store);
+        let (_, samples) = open(config(dir.path()), 0);
+        assert_eq!(vec![1000, 3000], timestamps(&samples));
+    }
+
+    #[test]
+    fn read_test() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(
+            Store::<SystemUtilization>::read(&config(dir.path()), 0)
+                .unwrap()
+                .is_empty()
+        );
+        let (mut store, _) = open(config(dir.path()), 0);
+        store.append(&sample(1000)).unwrap();
+        // Another process reads the store while the second record is half written
+        let record = encode_record(&sample(2000));
+        let (_, file) = store.current.as_mut().unwrap();
+        file.write_all(&record[..record.len() / 2]).unwrap();
+        let path = &segments(dir.path()).unwrap()[0].path;
+        let len = fs::metadata(path).unwrap().len();
+
+        let samples = Store::<SystemUtilization>::read(&config(dir.path()), 0).unwrap();
+        assert_eq!(vec![1000], timestamps(&samples));
+        assert_eq!(len, fs::metadata(path).unwrap().len());
+        let (_, file) = store.current.as_mut().unwrap();
+        file.write_all(&record[record.len() / 2..]).unwrap();
+        let samples = Store::<SystemUtilization>::read(&config(dir.path()), 0).unwrap();
+        assert_eq!(vec![1000, 2000], timestamps(&samples));
+    }
+
+    #[test]
+    fn corrupted_record_test() {
+        let mut data = encode_record(&sample(1000));
+        let second = encode_record(&sample(2000));
+        data.extend(&second);
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        let (samples, len) = decode_records(&data);
+        assert_eq!(vec![1000], timestamps(&samples));
+        assert_eq!(data.len() - second.len(), len);
+    }
+
+    #[test]
+    fn retention_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig {
+            max_age: Duration::from_secs(2 * 60 * 60),
+            ..config(dir.path())
+        };
+        let (mut store, _) = open(config.clone(), 0);
+        for hour in 0..5 {
+            store.append(&sample(hour * HOUR)).unwrap();
+        }
+        let starts: Vec<_> = segments(dir.path())
+            .unwrap()
+            .iter()
+            .map(|segment| segment.start)
+            .collect();
+        assert_eq!(vec![2 * HOUR, 3 * HOUR, 4 * HOUR], starts);
+
+        let size = fs::metadata(&segments(dir.path()).unwrap()[0].path)
+            .unwrap()
+            .len();
+        let (mut store, _) = open(
+            StoreConfig {
+                max_size: size,
+                ..config
+            },
+            0,
+        );
+        store.append(&sample(5 * HOUR)).unwrap();
+        assert_eq!(2, segments(dir.path()).unwrap().len());
+    }
+
+    #[test]
+    fn history_store_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = History::new();
+        let mut store = HistoryStore::open(config(dir.path()), &mut history, 0).unwrap();
+        for i in 0..6 {
+            let sample = sample(i * 10_000 + 5_000);
+            let rollups = history.push(sample.clone());
+            store.append(&sample, &rollups).unwrap();
+        }
+        drop(store);
+        assert!(dir.path().join("1s").is_dir());
+        assert!(dir.path().join("3600s").is_dir());
+
+        let mut restored = History::new();
+        HistoryStore::open(config(dir.path()), &mut restored, 60_000).unwrap();
+        let mut read = History::new();
+        HistoryStore::read(&config(dir.path()), &mut read, 60_000).unwrap();
+        let mems = |history: &mut History, to| -> Vec<u64> {
+            history
+                .range(0, to, 1)
+                .iter()
+                .map(|rollup| rollup.avg.mem)
+                .collect()
+        };
+        // Samples at full resolution
+        assert_eq!(6, mems(&mut restored, 60_000).len());
+        assert_eq!(mems(&mut history, 60_000), mems(&mut restored, 60_000));
+        // Rollups of the 10s tier, the last sample is in the bucket being filled
+        assert_eq!(6, mems(&mut restored, 2 * HOUR).len());
+        assert_eq!(mems(&mut history, 2 * HOUR), mems(&mut restored, 2 * HOUR));
+        assert_eq!(mems(&mut restored, 2 * HOUR), mems(&mut read, 2 * HOUR));
+    }
+}
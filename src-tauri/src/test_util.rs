@@ -0,0 +1,88 @@
+//! Samples for the tests of several modules
+
+use shared::*;
+
+/// A sample of two cores, a GPU, a disk, a network interface and three sensors. The disk reads
+/// and the interface receives `timestamp` bytes per second, so that samples can be told apart.
+pub fn sample(timestamp: u64) -> SystemUtilization {
+    SystemUtilization {
+        timestamp,
+        cpus: vec![
+            CpuCore {
+                usage: 50.0,
+                ..Default::default()
+            },
+            CpuCore {
+                usage: 25.0,
+                ..Default::default()
+            },
+        ],
+        cpu_usage: 37.5,
+        mem: 3 << 30,
+        mem_max: 16 << 30,
+        gpus: vec![Gpu {
+            usage: 42,
+            temp: 60,
+            throttle_reasons: vec!["power cap".to_owned()],
+            ..Default::default()
+        }],
+        disks: vec![disk("sda", timestamp)],
+        // Two drives with the same chip and label
+        sensors: vec![
+            Sensor {
+                chip: "coretemp".to_owned(),
+                label: "Package id 0".to_owned(),
+                temp: 45.5,
+                ..Default::default()
+            },
+            Sensor {
+                chip: "nvme".to_owned(),
+                device: "nvme0".to_owned(),
+                label: "Composite".to_owned(),
+                temp: 38.0,
+                ..Default::default()
+            },
+            Sensor {
+                chip: "nvme".to_owned(),
+                device: "nvme1".to_owned(),
+                label: "Composite".to_owned(),
+                temp: 40.0,
+                ..Default::default()
+            },
+        ],
+        network: Network {
+            down: 2048,
+            interfaces: vec![NetworkInterface {
+                ips: vec!["192.168.1.2/24".to_owned()],
+                errors_down: 2,
+                included: true,
+                ..interface("eth0", timestamp)
+            }],
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// A block device reading `read_bytes` per second
+pub fn disk(name: &str, read_bytes: u64) -> DiskDevice {
+    DiskDevice {
+        name: name.to_owned(),
+        read_bytes,
+        ..Default::default()
+    }
+}
+
+/// A network interface receiving `down` bytes per second
+pub fn interface(name: &str, down: u64) -> NetworkInterface {
+    NetworkInterface {
+        name: name.to_owned(),
+        down,
+        ..Default::default()
+    }
+}
+
+/// When each of `samples` was taken
+pub fn timestamps(samples: &[SystemUtilization]) -> Vec<u64> {
+    samples.iter().map(|sample| sample.timestamp).collect()
+}
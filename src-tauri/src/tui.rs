@@ -0,0 +1,527 @@
+use shared::{
+    Rollup, SAMPLE_INTERVAL, SystemInfo, SystemUtilization, TIME_OPTIONS, print_bytes, print_secs,
+};
+
+use std::{cell::RefCell, io, time::Instant};
+
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style, Stylize},
+    symbols::Marker,
+    text::Line,
+    widgets::{Axis, Bar, BarChart, Block, Chart, Dataset, GraphType, Paragraph, Tabs},
+};
+
+use crate::{history::History, monitor::SystemMonitor};
+
+/// Views of the terminal UI, in the order the keys cycle through them
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum View {
+    Cpu,
+    Memory,
+    Gpu,
+    Disk,
+    Network,
+}
+
+impl View {
+    const ALL: [View; 5] = [
+        View::Cpu,
+        View::Memory,
+        View::Gpu,
+        View::Disk,
+        View::Network,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            View::Cpu => "CPU",
+            View::Memory => "Memory",
+            View::Gpu => "GPU",
+            View::Disk => "Disk",
+            View::Network => "Network",
+        }
+    }
+
+    fn index(self) -> usize {
+        View::ALL.iter().position(|view| *view == self).unwrap()
+    }
+}
+
+/// A line of a chart, split where the history has gaps so that they aren't bridged
+struct Series {
+    name: &'static str,
+    color: Color,
+    segments: Vec<Vec<(f64, f64)>>,
+}
+
+impl Series {
+    /// `value` of the averages, at seconds before `to`
+    fn new(
+        name: &'static str,
+        color: Color,
+        history: &[Rollup],
+        to: u64,
+        resolution: u64,
+        value: impl Fn(&SystemUtilization) -> f64,
+    ) -> Self {
+        let mut segments: Vec<Vec<_>> = Vec::new();
+        let mut previous: Option<&Rollup> = None;
+        for rollup in history {
+            if previous.is_none_or(|previous| previous.gap_until(rollup, 2 * resolution)) {
+                segments.push(Vec::new());
+            }
+            let x = (rollup.avg.timestamp as f64 - to as f64) / 1000.0;
+            segments.last_mut().unwrap().push((x, value(&rollup.avg)));
+            previous = Some(rollup);
+        }
+        Self {
+            name,
+            color,
+            segments,
+        }
+    }
+
+    /// The highest value, at least `min`
+    fn max(&self, min: f64) -> f64 {
+        self.segments
+            .iter()
+            .flatten()
+            .map(|(_, y)| *y)
+            .fold(min, f64::max)
+    }
+}
+
+fn cpu_usage(sample: &SystemUtilization) -> f32 {
+    sample.cpus.iter().map(|cpu| cpu.usage).sum::<f32>() / sample.cpus.len().max(1) as f32
+}
+
+fn print_rate(value: f64) -> String {
+    format!("{}/s", print_bytes(value as u64))
+}
+
+fn print_percent(value: f64) -> String {
+    format!("{value:.0}%")
+}
+
+/// State of the terminal UI
+struct Tui {
+    sys_info: SystemInfo,
+    /// Queried while drawing, see [`History::range`]
+    history: RefCell<History>,
+    /// The latest sample
+    latest: SystemUtilization,
+    view: View,
+    /// Index of the period shown in [`TIME_OPTIONS`]
+    period: usize,
+    quit: bool,
+}
+
+impl Tui {
+    fn new(sys_info: SystemInfo, history: History) -> Self {
+        Self {
+            sys_info,
+            history: RefCell::new(history),
+            latest: SystemUtilization::default(),
+            view: View::Cpu,
+            period: 0,
+            quit: false,
+        }
+    }
+
+    fn push(&mut self, sample: SystemUtilization) {
+        self.latest = sample.clone();
+        self.history.get_mut().push(sample);
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        let view = self.view.index();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => {
+                self.view = View::ALL[(view + 1) % View::ALL.len()];
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                self.view = View::ALL[(view + View::ALL.len() - 1) % View::ALL.len()];
+            }
+            KeyCode::Char(digit @ '1'..='9') => {
+                let index = digit as usize - '1' as usize;
+                if let Some(view) = View::ALL.get(index) {
+                    self.view = *view;
+                }
+            }
+            KeyCode::Up | KeyCode::Char('+') | KeyCode::Char('k') => {
+                self.period = (self.period + 1).min(TIME_OPTIONS.len() - 1);
+            }
+            KeyCode::Down | KeyCode::Char('-') | KeyCode::Char('j') => {
+                self.period = self.period.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+
+    /// Averages over the period shown, `points` of them at most, and the length of a bucket
+    fn history(&self, points: u16) -> (Vec<Rollup>, u64) {
+        let period = TIME_OPTIONS[self.period] * 1000;
+        let resolution = period.div_ceil(points.max(1) as u64 * 1000) * 1000;
+        let to = self.latest.timestamp;
+        // Buckets are centered on `to`, leave out the one half outside of the period
+        let from = to.saturating_sub(period - resolution / 2);
+        let history = self.history.borrow_mut().range(from, to, resolution);
+        (history, resolution)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [tabs, main, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [tabs, period] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Length(16)]).areas(tabs);
+
+        frame.render_widget(
+            Tabs::new(View::ALL.map(View::title))
+                .select(self.view.index())
+                .highlight_style(Style::new().bold().reversed()),
+            tabs,
+        );
+        frame.render_widget(
+            Line::from(format!("Period: {}", print_secs(TIME_OPTIONS[self.period])))
+                .right_aligned(),
+            period,
+        );
+        frame.render_widget(Line::from("←/→ view  ↑/↓ period  q quit").dark_gray(), help);
+
+        match self.view {
+            View::Cpu => self.draw_cpu(frame, main),
+            View::Memory => self.draw_memory(frame, main),
+            View::Gpu => self.draw_gpu(frame, main),
+            View::Disk => self.draw_disk(frame, main),
+            View::Network => self.draw_network(frame, main),
+        }
+    }
+
+    /// Draws `series` over the period, from 0 to `y_max`
+    fn draw_chart(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        title: String,
+        series: &[Series],
+        y_max: f64,
+        print_value: fn(f64) -> String,
+    ) {
+        let datasets = series
+            .iter()
+            .flat_map(|series| {
+                series.segments.iter().enumerate().map(|(i, segment)| {
+                    let dataset = Dataset::default()
+                        .marker(Marker::Braille)
+                        .graph_type(GraphType::Line)
+                        .style(series.color)
+                        .data(segment);
+                    // A single entry in the legend
+                    if i == 0 {
+                        dataset.name(series.name)
+                    } else {
+                        dataset
+                    }
+                })
+            })
+            .collect();
+        let period = TIME_OPTIONS[self.period];
+        let chart = Chart::new(datasets)
+            .block(Block::bordered().title(title))
+            .x_axis(
+                Axis::default()
+                    .bounds([-(period as f64), 0.0])
+                    .labels([format!("-{}", print_secs(period)), "now".to_owned()]),
+            )
+            .y_axis(Axis::default().bounds([0.0, y_max]).labels([
+                print_value(0.0),
+                print_value(y_max / 2.0),
+                print_value(y_max),
+            ]));
+        frame.render_widget(chart, area);
+    }
+
+    fn draw_cpu(&self, frame: &mut Frame, area: Rect) {
+        let [chart, cores] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(8)]).areas(area);
+        let (history, resolution) = self.history(chart.width * 2);
+        let to = self.latest.timestamp;
+        let series = [
+            Series::new("usage", Color::Cyan, &history, to, resolution, |util| {
+                cpu_usage(util) as f64
+            }),
+            Series::new("kernel", Color::Red, &history, to, resolution, |util| {
+                util.cpu_times.kernel() as f64
+            }),
+        ];
+        let title = format!(
+            "{} {:.1}%",
+            self.sys_info.cpu_brand,
+            cpu_usage(&self.latest)
+        );
+        self.draw_chart(frame, chart, title, &series, 100.0, print_percent);
+
+        let bars: Vec<_> = self
+            .latest
+            .cpus
+            .iter()
+            .enumerate()
+            .map(|(i, cpu)| {
+                Bar::with_label(i.to_string(), cpu.usage as u64)
+                    .text_value(format!("{:.0}", cpu.usage))
+            })
+            .collect();
+        frame.render_widget(
+            BarChart::vertical(bars)
+                .block(Block::bordered().title("Cores"))
+                .max(100)
+                .bar_width(3)
+                .bar_style(Color::Cyan),
+            cores,
+        );
+    }
+
+    fn draw_memory(&self, frame: &mut Frame, area: Rect) {
+        let (history, resolution) = self.history(area.width * 2);
+        let to = self.latest.timestamp;
+        let memory = &self.latest.memory;
+        let series = [
+            Series::new("used", Color::Magenta, &history, to, resolution, |util| {
+                util.mem as f64
+            }),
+            Series::new("swap", Color::Yellow, &history, to, resolution, |util| {
+                util.memory.swap_used as f64
+            }),
+        ];
+        let title = format!(
+            "Memory {} / {}, swap {} / {}",
+            print_bytes(self.latest.mem),
+            print_bytes(self.latest.mem_max),
+            print_bytes(memory.swap_used),
+            print_bytes(memory.swap_total)
+        );
+        let y_max = self.latest.mem_max.max(memory.swap_total).max(1) as f64;
+        self.draw_chart(frame, area, title, &series, y_max, |value| {
+            print_bytes(value as u64)
+        });
+    }
+
+    fn draw_gpu(&self, frame: &mut Frame, area: Rect) {
+        let gpus = &self.latest.gpus;
+        if gpus.is_empty() {
+            frame.render_widget(
+                Paragraph::new("No GPUs found").block(Block::bordered().title("GPU")),
+                area,
+            );
+            return;
+        }
+        let areas = Layout::vertical(gpus.iter().map(|_| Constraint::Fill(1))).split(area);
+        let (history, resolution) = self.history(area.width * 2);
+        let to = self.latest.timestamp;
+        for (i, (gpu, area)) in gpus.iter().zip(areas.iter()).enumerate() {
+            let series = [
+                Series::new("usage", Color::Green, &history, to, resolution, |util| {
+                    util.gpus.get(i).map_or(0.0, |gpu| gpu.usage as f64)
+                }),
+                Series::new(
+                    "memory busy",
+                    Color::Blue,
+                    &history,
+                    to,
+                    resolution,
+                    |util| util.gpus.get(i).map_or(0.0, |gpu| gpu.mem_busy as f64),
+                ),
+            ];
+            let name = self.sys_info.gpu_names.get(i).map_or("GPU", String::as_str);
+            let title = format!(
+                "{name} {}% {} ℃, VRAM {} / {}",
+                gpu.usage,
+                gpu.temp,
+                print_bytes(gpu.vram_used),
+                print_bytes(gpu.vram_total)
+            );
+            self.draw_chart(frame, *area, title, &series, 100.0, print_percent);
+        }
+    }
+
+    fn draw_disk(&self, frame: &mut Frame, area: Rect) {
+        let (history, resolution) = self.history(area.width * 2);
+        let to = self.latest.timestamp;
+        let series = [
+            Series::new("read", Color::Green, &history, to, resolution, |util| {
+                util.disk.read_bytes as f64
+            }),
+            Series::new("write", Color::Red, &history, to, resolution, |util| {
+                util.disk.writen_bytes as f64
+            }),
+        ];
+        let disk = &self.latest.disk;
+        let title = format!(
+            "Disk read {}, write {}",
+            print_rate(disk.read_bytes as f64),
+            print_rate(disk.writen_bytes as f64)
+        );
+        let y_max = series[1].max(series[0].max(1024.0));
+        self.draw_chart(frame, area, title, &series, y_max, print_rate);
+    }
+
+    fn draw_network(&self, frame: &mut Frame, area: Rect) {
+        let (history, resolution) = self.history(area.width * 2);
+        let to = self.latest.timestamp;
+        let series = [
+            Series::new("down", Color::Green, &history, to, resolution, |util| {
+                util.network.down as f64
+            }),
+            Series::new("up", Color::Red, &history, to, resolution, |util| {
+                util.network.up as f64
+            }),
+        ];
+        let network = &self.latest.network;
+        let title = format!(
+            "Network down {}, up {}",
+            print_rate(network.down as f64),
+            print_rate(network.up as f64)
+        );
+        let y_max = series[1].max(series[0].max(1024.0));
+        self.draw_chart(frame, area, title, &series, y_max, print_rate);
+    }
+
+    /// Samples the system every [`SAMPLE_INTERVAL`] and redraws until quit, handling keys in
+    /// between
+    fn run(
+        &mut self,
+        monitor: &mut SystemMonitor,
+        terminal: &mut DefaultTerminal,
+    ) -> io::Result<()> {
+        // Like the sampler, so that the rates of the first sample cover a whole interval
+        let mut next = Instant::now() + SAMPLE_INTERVAL;
+        while !self.quit {
+            let now = Instant::now();
+            if next <= now {
+                self.push(monitor.get_stats());
+                // Starts over after falling behind, like the sampler
+                next = (next + SAMPLE_INTERVAL).max(now);
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(next.saturating_duration_since(Instant::now()))?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.handle_key(key);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Shows the system in the terminal, starting with `history`, until `q` is pressed
+pub fn run(monitor: &mut SystemMonitor, history: History) -> io::Result<()> {
+    let mut tui = Tui::new(monitor.sys_info.clone(), history);
+    let mut terminal = ratatui::try_init()?;
+    let result = tui.run(monitor, &mut terminal);
+    // Gives the terminal back even if drawing failed
+    let restored = ratatui::try_restore();
+    result.and(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ratatui::{Terminal, backend::TestBackend};
+
+    use crate::test_util::sample;
+
+    fn tui() -> Tui {
+        let sys_info = SystemInfo {
+            cpu_brand: "Test CPU".to_owned(),
+            gpu_names: vec!["Test GPU".to_owned()],
+            ..Default::default()
+        };
+        let mut tui = Tui::new(sys_info, History::new());
+        for i in 1..=10 {
+            tui.push(sample(i * 1000));
+        }
+        tui
+    }
+
+    fn press(tui: &mut Tui, code: KeyCode) {
+        tui.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    fn render(tui: &Tui) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        terminal.draw(|frame| tui.draw(frame)).unwrap();
+        terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect()
+    }
+
+    #[test]
+    fn keys_test() {
+        let mut tui = tui();
+        press(&mut tui, KeyCode::Left);
+        assert_eq!(View::Network, tui.view);
+        press(&mut tui, KeyCode::Tab);
+        assert_eq!(View::Cpu, tui.view);
+        press(&mut tui, KeyCode::Char('3'));
+        assert_eq!(View::Gpu, tui.view);
+        press(&mut tui, KeyCode::Char('9'));
+        assert_eq!(View::Gpu, tui.view);
+
+        press(&mut tui, KeyCode::Down);
+        assert_eq!(0, tui.period);
+        for _ in 0..TIME_OPTIONS.len() {
+            press(&mut tui, KeyCode::Up);
+        }
+        assert_eq!(TIME_OPTIONS.len() - 1, tui.period);
+
+        assert!(!tui.quit);
+        tui.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert!(tui.quit);
+    }
+
+    #[test]
+    fn history_test() {
+        let mut tui = tui();
+        let (history, resolution) = tui.history(200);
+        assert_eq!(1000, resolution);
+        assert_eq!(10, history.len());
+        // 5 minutes in 100 points
+        tui.period = 1;
+        assert_eq!(3000, tui.history(100).1);
+    }
+
+    #[test]
+    fn draw_test() {
+        let mut tui = tui();
+        let screen = render(&tui);
+        assert!(screen.contains("Test CPU 37.5%"));
+        assert!(screen.contains("Period: 1 min"));
+
+        let expected = [
+            "Memory 3.0 GiB / 16.0 GiB",
+            "Test GPU 42%",
+            "Disk read 0.0 B/s",
+            "Network down 2.0 KiB/s",
+        ];
+        for expected in expected {
+            press(&mut tui, KeyCode::Right);
+            let screen = render(&tui);
+            assert!(screen.contains(expected), "{expected} not in {screen}");
+        }
+    }
+}
@@ -1,27 +1,162 @@
 use shared::*;
 
-use std::{collections::VecDeque, time::Duration};
+use std::collections::{HashSet, VecDeque};
 
 use leptos::{prelude::*, task::spawn_local};
 use plotly::{
     Configuration, Layout, Plot, Scatter,
     color::{Rgb, Rgba},
-    common::{AxisSide, Fill, Marker, Title},
-    layout::{Axis, AxisRange, Margin},
+    common::{AxisSide, Fill, Line, Marker, Mode, Title},
+    layout::{Axis, AxisRange, AxisType, Margin},
 };
 
 use crate::plotly_bindings::react;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"])]
     async fn invoke(cmd: &str, args: JsValue) -> JsValue;
+
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"], js_name = invoke, catch)]
+    async fn try_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "event"])]
+    async fn listen(event: &str, handler: &Closure<dyn FnMut(JsValue)>) -> JsValue;
+}
+
+/// What Tauri passes to event listeners
+#[derive(Deserialize)]
+struct TauriEvent<T> {
+    payload: T,
+}
+
+/// Arguments of the `get_history` command, in milliseconds
+#[derive(Serialize)]
+struct HistoryArgs {
+    from: u64,
+    to: u64,
+    resolution: u64,
+}
+
+/// Arguments of the `export_history` command
+#[derive(Serialize)]
+struct ExportArgs {
+    from: u64,
+    to: u64,
+    /// `csv`, `jsonl` or `parquet`
+    format: String,
+}
+
+/// Arguments of the `get_recording` command, in milliseconds
+#[derive(Serialize)]
+struct RecordingArgs {
+    from: u64,
+    to: u64,
+}
+
+/// Resolution of samples that weren't downsampled, in milliseconds
+const RAW_RESOLUTION: u64 = SAMPLE_INTERVAL.as_millis() as u64;
+
+/// Shifts a Unix timestamp in milliseconds by the local UTC offset, since plotly shows dates in UTC
+fn local_time(timestamp: u64) -> f64 {
+    let offset = js_sys::Date::new(&JsValue::from_f64(timestamp as f64)).get_timezone_offset();
+    timestamp as f64 - offset * 60_000.0
+}
+
+/// An x axis of clock times over the `period` milliseconds up to `to`
+fn time_axis(to: u64, period: u64) -> Axis {
+    let range = AxisRange::new(local_time(to.saturating_sub(period)), local_time(to));
+    Axis::new().type_(AxisType::Date).range(range)
+}
+
+/// When the newest sample of the history was taken
+fn latest(history: &[Rollup]) -> u64 {
+    history.last().map_or(0, |rollup| rollup.max.timestamp)
+}
+
+/// The x axis of the small graphs, which show `max_history` raw samples without ticks
+fn mini_axis(history: &[Rollup], max_history: usize) -> Axis {
+    let period = (max_history as u64).saturating_sub(1) * RAW_RESOLUTION;
+    time_axis(latest(history), period).tick_values(vec![])
+}
+
+/// A value over time: the mean over every bucket, with its lowest and highest value.
+/// Gaps in sampling have no value, which breaks the lines.
+#[derive(Clone)]
+struct Series<T> {
+    /// Time of every bucket, see [`local_time`]
+    x: Vec<f64>,
+    min: Vec<Option<T>>,
+    avg: Vec<Option<T>>,
+    max: Vec<Option<T>>,
+}
+
+/// `resolution` is the length of a bucket in milliseconds. Sampling that stopped for longer than
+/// two buckets, e.g. while the system was asleep, is drawn as a gap instead of a line across it.
+fn series<T>(
+    history: &[Rollup],
+    resolution: u64,
+    value: impl Fn(&SystemUtilization) -> T,
+) -> Series<T> {
+    let mut series = Series {
+        x: Vec::new(),
+        min: Vec::new(),
+        avg: Vec::new(),
+        max: Vec::new(),
+    };
+    let mut previous: Option<&Rollup> = None;
+    for rollup in history {
+        if let Some(previous) = previous
+            && previous.gap_until(rollup, 2 * resolution)
+        {
+            let middle = previous.max.timestamp / 2 + rollup.min.timestamp / 2;
+            series.x.push(local_time(middle));
+            series.min.push(None);
+            series.avg.push(None);
+            series.max.push(None);
+        }
+        series.x.push(local_time(rollup.avg.timestamp));
+        series.min.push(Some(value(&rollup.min)));
+        series.avg.push(Some(value(&rollup.avg)));
+        series.max.push(Some(value(&rollup.max)));
+        previous = Some(rollup);
+    }
+    series
+}
+
+/// Samples that weren't downsampled, which are drawn without an envelope
+fn single_rollups(sys_util_history: &[SystemUtilization]) -> Vec<Rollup> {
+    sys_util_history.iter().cloned().map(Rollup::from).collect()
 }
 
-// Assumes that the number of cpus doesn't change and may panic otherwise.
-fn plot_cpu(sys_util_history: &[SystemUtilization], max_history: usize) -> Plot {
+/// Shades the band between the lowest and highest values, if they differ
+fn add_envelope<T: Clone + PartialEq + Serialize + 'static>(
+    plot: &mut Plot,
+    series: &Series<T>,
+    color: Rgb,
+) {
+    if series.min == series.max {
+        return;
+    }
+    let line = Line::new().width(0.0).color(color);
+    let min = Scatter::new(series.x.clone(), series.min.clone())
+        .show_legend(false)
+        .mode(Mode::Lines)
+        .line(line.clone());
+    let max = Scatter::new(series.x.clone(), series.max.clone())
+        .show_legend(false)
+        .mode(Mode::Lines)
+        .line(line)
+        .fill(Fill::ToNextY)
+        .opacity(0.3);
+    plot.add_trace(min);
+    plot.add_trace(max);
+}
+
+// Assumes that the number of cpus doesn't change.
+fn plot_cpu(sys_util_history: &[Rollup], resolution: u64) -> Plot {
     let mut plot = Plot::new();
 
     let config = Configuration::new().static_plot(true).responsive(true);
@@ -29,22 +164,8 @@ fn plot_cpu(sys_util_history: &[SystemUtilization], max_history: usize) -> Plot
     let layout = Layout::new().auto_size(true);
     plot.set_layout(layout);
 
-    let cpu_history = sys_util_history
-        .iter()
-        .map(|util| util.cpus.clone())
-        .collect::<Vec<_>>();
-
-    if let Some(history_point) = cpu_history.first() {
-        let cpu_count = history_point.len();
-        let mut traces: Vec<Vec<f32>> = vec![Vec::new(); cpu_count];
-
-        for history_point in &cpu_history {
-            for (id, cpu) in history_point.iter().enumerate() {
-                traces[id].push(cpu.usage / cpu_count as f32);
-            }
-        }
-        let lower_bound = (max_history - cpu_history.len()).max(0);
-        let x = (lower_bound..max_history).collect::<Vec<_>>();
+    if let Some(history_point) = sys_util_history.first() {
+        let cpu_count = history_point.avg.cpus.len();
         let stack_group = "stack_group";
         let colors = [
             Rgb::new(74, 85, 162),
@@ -54,9 +175,12 @@ fn plot_cpu(sys_util_history: &[SystemUtilization], max_history: usize) -> Plot
             Rgb::new(160, 191, 224),
             Rgb::new(120, 149, 203),
         ];
-        for (i, y) in traces.iter().enumerate() {
-            let color = colors[i % colors.len()];
-            let trace = Scatter::new(x.clone(), y.clone())
+        for id in 0..cpu_count {
+            let color = colors[id % colors.len()];
+            let core = series(sys_util_history, resolution, |util| {
+                util.cpus.get(id).map_or(0.0, |cpu| cpu.usage) / cpu_count as f32
+            });
+            let trace = Scatter::new(core.x, core.avg)
                 // Line smoothing
                 // .line(
                 //     plotly::common::Line::new()
@@ -69,13 +193,34 @@ fn plot_cpu(sys_util_history: &[SystemUtilization], max_history: usize) -> Plot
                 .marker(Marker::new().color(color));
             plot.add_trace(trace);
         }
+
+        let usage = series(sys_util_history, resolution, |util| util.cpu_usage);
+        add_envelope(&mut plot, &usage, colors[0]);
     }
     plot
 }
 
-fn plot_generic_many<T: Clone + Serialize + 'static>(
-    values: &[Vec<T>],
-    max_history: usize,
+fn plot_cpu_core(sys_util_history: &[Rollup], resolution: u64, core: usize) -> Plot {
+    let plot_values = series(sys_util_history, resolution, |util| {
+        util.cpus.get(core).map_or(0.0, |cpu| cpu.usage)
+    });
+    plot_generic_many(&[plot_values], &[Rgb::new(74, 85, 162)], Fill::ToZeroY)
+}
+
+/// Adds kernel time as a darker area on top of the stacked usage of [`plot_cpu`]
+fn add_kernel_times(plot: &mut Plot, sys_util_history: &[Rollup], resolution: u64) {
+    let kernel = series(sys_util_history, resolution, |util| util.cpu_times.kernel());
+    let trace = Scatter::new(kernel.x, kernel.avg)
+        .show_legend(false)
+        .fill(Fill::ToZeroY)
+        .fill_color(Rgba::new(30, 40, 110, 0.6))
+        .marker(Marker::new().color(Rgb::new(30, 40, 110)).size(1));
+    plot.add_trace(trace);
+}
+
+/// Draws the mean of every series as a line, in an envelope of its lowest and highest values
+fn plot_generic_many<T: Clone + PartialEq + Serialize + 'static>(
+    values: &[Series<T>],
     colors: &[Rgb],
     fill: Fill,
 ) -> Plot {
@@ -85,11 +230,10 @@ fn plot_generic_many<T: Clone + Serialize + 'static>(
     let layout = Layout::new().auto_size(true);
     plot.set_layout(layout);
 
-    let lower_bound = (max_history - values[0].len()).max(0);
-    let x = (lower_bound..max_history).collect::<Vec<_>>();
-    for (i, y) in values.iter().enumerate() {
+    for (i, series) in values.iter().enumerate() {
         let color = colors[i % colors.len()];
-        let trace = Scatter::new(x.clone(), y.clone())
+        add_envelope(&mut plot, series, color);
+        let trace = Scatter::new(series.x.clone(), series.avg.clone())
             .show_legend(false)
             .marker(Marker::new().color(color).size(1))
             .fill(fill.clone());
@@ -100,24 +244,44 @@ fn plot_generic_many<T: Clone + Serialize + 'static>(
     plot
 }
 
-fn plot_mem(sys_util_history: &[SystemUtilization], max_history: usize) -> Plot {
-    let plot_values = sys_util_history.iter().map(|util| util.mem).collect();
+fn plot_mem(sys_util_history: &[Rollup], resolution: u64) -> Plot {
+    let plot_values = series(sys_util_history, resolution, |util| util.mem);
     let color = Rgb::new(101, 39, 190);
 
-    plot_generic_many(&[plot_values], max_history, &[color], Fill::ToZeroY)
+    plot_generic_many(&[plot_values], &[color], Fill::ToZeroY)
 }
 
-fn plot_gpu(sys_util_history: &[SystemUtilization], max_history: usize, gpu_id: usize) -> Plot {
-    let plot_values = sys_util_history
-        .iter()
-        .map(|util| util.gpus[gpu_id].usage)
+const SENSOR_COLORS: [(u8, u8, u8); 6] = [
+    (214, 39, 40),
+    (255, 127, 14),
+    (101, 39, 190),
+    (0, 128, 43),
+    (120, 149, 203),
+    (140, 86, 75),
+];
+
+// Assumes that the set of sensors doesn't change, like `plot_cpu`.
+fn plot_sensors(sys_util_history: &[Rollup], resolution: u64) -> Plot {
+    let sensor_count = sys_util_history
+        .first()
+        .map_or(0, |rollup| rollup.avg.sensors.len());
+    let values: Vec<_> = (0..sensor_count.max(1))
+        .map(|id| {
+            series(sys_util_history, resolution, |util| {
+                util.sensors.get(id).map_or(0.0, |sensor| sensor.temp)
+            })
+        })
         .collect();
-    plot_generic_many(
-        &[plot_values],
-        max_history,
-        &[Rgb::new(120, 149, 203)],
-        Fill::ToZeroY,
-    )
+    let colors = SENSOR_COLORS.map(|(r, g, b)| Rgb::new(r, g, b));
+
+    plot_generic_many(&values, &colors, Fill::None)
+}
+
+fn plot_gpu(sys_util_history: &[Rollup], resolution: u64, gpu_id: usize) -> Plot {
+    let plot_values = series(sys_util_history, resolution, |util| {
+        util.gpus.get(gpu_id).map_or(0.0, |gpu| gpu.usage as f64)
+    });
+    plot_generic_many(&[plot_values], &[Rgb::new(120, 149, 203)], Fill::ToZeroY)
 }
 
 const COLOR_READ: (u8, u8, u8) = (0, 128, 43);
@@ -135,18 +299,32 @@ const COLOR_WRITE_HTML: &str = const_format::formatcp!(
     COLOR_WRITE.2
 );
 
-fn plot_disk(sys_util_history: &[SystemUtilization], max_history: usize) -> Plot {
-    let read = sys_util_history
-        .iter()
-        .map(|util| util.disk.read_bytes)
-        .collect();
-    let write = sys_util_history
-        .iter()
-        .map(|util| util.disk.writen_bytes)
-        .collect();
+fn plot_disk(sys_util_history: &[Rollup], resolution: u64) -> Plot {
+    let read = series(sys_util_history, resolution, |util| util.disk.read_bytes);
+    let write = series(sys_util_history, resolution, |util| util.disk.writen_bytes);
+    plot_generic_many(
+        &[read, write],
+        &[
+            Rgb::new(COLOR_READ.0, COLOR_READ.1, COLOR_READ.2),
+            Rgb::new(COLOR_WRITE.0, COLOR_WRITE.1, COLOR_WRITE.2),
+        ],
+        Fill::None,
+    )
+}
+
+fn find_disk<'a>(sys_util: &'a SystemUtilization, name: &str) -> Option<&'a DiskDevice> {
+    sys_util.disks.iter().find(|disk| disk.name == name)
+}
+
+fn plot_disk_device(sys_util_history: &[Rollup], resolution: u64, name: &str) -> Plot {
+    let read = series(sys_util_history, resolution, |util| {
+        find_disk(util, name).map_or(0, |disk| disk.read_bytes)
+    });
+    let write = series(sys_util_history, resolution, |util| {
+        find_disk(util, name).map_or(0, |disk| disk.written_bytes)
+    });
     plot_generic_many(
         &[read, write],
-        max_history,
         &[
             Rgb::new(COLOR_READ.0, COLOR_READ.1, COLOR_READ.2),
             Rgb::new(COLOR_WRITE.0, COLOR_WRITE.1, COLOR_WRITE.2),
@@ -166,21 +344,32 @@ const COLOR_DOWN_HTML: &str = const_format::formatcp!(
 const COLOR_UP_HTML: &str =
     const_format::formatcp!("color: rgb({}, {}, {})", COLOR_UP.0, COLOR_UP.1, COLOR_UP.2);
 
-fn plot_network(sys_util_history: &[SystemUtilization], max_history: usize) -> Plot {
-    let down = sys_util_history
-        .iter()
-        .map(|util| util.network.down)
-        .collect();
-    let up = sys_util_history
-        .iter()
-        .map(|util| util.network.up)
-        .collect();
+/// Download and upload speed of either a single interface or the total
+fn network_speed(sys_util: &SystemUtilization, interface: Option<&str>) -> (u64, u64) {
+    match interface {
+        Some(name) => sys_util
+            .network
+            .interfaces
+            .iter()
+            .find(|interface| interface.name == name)
+            .map_or((0, 0), |interface| (interface.down, interface.up)),
+        None => (sys_util.network.down, sys_util.network.up),
+    }
+}
+
+fn plot_network(sys_util_history: &[Rollup], resolution: u64, interface: Option<&str>) -> Plot {
+    let down = series(sys_util_history, resolution, |util| {
+        network_speed(util, interface).0
+    });
+    let up = series(sys_util_history, resolution, |util| {
+        network_speed(util, interface).1
+    });
     let colors = [
         Rgb::new(COLOR_DOWN.0, COLOR_DOWN.1, COLOR_DOWN.2),
         Rgb::new(COLOR_UP.0, COLOR_UP.1, COLOR_UP.2),
     ];
 
-    plot_generic_many(&[down, up], max_history, &colors, Fill::None)
+    plot_generic_many(&[down, up], &colors, Fill::None)
 }
 
 #[component]
@@ -190,15 +379,14 @@ fn PlotCpuMini(
 ) -> impl IntoView {
     let div_id = "side-cpu";
     Effect::new(move |_| {
-        let mut plot = plot_cpu(&sys_util_history.get(), max_history.get());
+        let history = single_rollups(&sys_util_history.get());
+        let mut plot = plot_cpu(&history, RAW_RESOLUTION);
 
         let y_ticks = vec![0.0, 20.0, 40.0, 60.0, 80.0, 100.0];
         let y_axis = Axis::new()
             .range(AxisRange::new(0, 100))
             .tick_values(y_ticks);
-        let x_axis = Axis::new()
-            .range(AxisRange::new(0, max_history.get() - 1))
-            .tick_values(vec![]);
+        let x_axis = mini_axis(&history, max_history.get());
         let margin = Margin::new().left(0).right(0).top(0).bottom(0);
         let layout = plot
             .layout()
@@ -223,9 +411,8 @@ fn PlotMemMini(
 ) -> impl IntoView {
     let div_id = "side-mem";
     Effect::new(move |_| {
-        let max_history = max_history.get();
-
-        let mut plot = plot_mem(&sys_util_history.get(), max_history);
+        let history = single_rollups(&sys_util_history.get());
+        let mut plot = plot_mem(&history, RAW_RESOLUTION);
 
         let max_mem = if let Some(sys_util) = sys_util_history.get().first() {
             sys_util.mem_max
@@ -233,9 +420,7 @@ fn PlotMemMini(
             0
         };
         let y_axis = Axis::new().range(AxisRange::new(0, max_mem));
-        let x_axis = Axis::new()
-            .range(AxisRange::new(0, max_history - 1))
-            .tick_values(vec![]);
+        let x_axis = mini_axis(&history, max_history.get());
         let margin = Margin::new().left(0).right(0).top(0).bottom(0);
         let layout = plot
             .layout()
@@ -268,13 +453,11 @@ fn PlotGpusMini(
                 {
                     let div_id = div_id.clone();
                     Effect::new(move |_| {
-                        let max_history = max_history.get();
-                        let mut plot = plot_gpu(&sys_util_history.get(), max_history, gpu_id);
+                        let history = single_rollups(&sys_util_history.get());
+                        let mut plot = plot_gpu(&history, RAW_RESOLUTION, gpu_id);
                         let y_ticks = vec![0.0, 20.0, 40.0, 60.0, 80.0, 100.0];
                         let y_axis = Axis::new().range(AxisRange::new(0, 100)).tick_values(y_ticks);
-                        let x_axis = Axis::new()
-                            .range(AxisRange::new(0, max_history - 1))
-                            .tick_values(vec![]);
+                        let x_axis = mini_axis(&history, max_history.get());
                         let margin = Margin::new().left(0).right(0).top(0).bottom(0);
                         let layout = plot
                             .layout()
@@ -290,12 +473,15 @@ fn PlotGpusMini(
                     });
                 }
                 let gpu_descr = move || {
-                    if let Some(last) = sys_util_history.get().last() {
-                        let gpu = last.gpus[gpu_id].clone();
-                        format!("{}% ({} ℃)", gpu.usage, gpu.temp)
-                    } else {
-                        String::new()
-                    }
+                    // The GPU may have been unplugged since the first sample
+                    sys_util_history.with(|history| {
+                        history
+                            .last()
+                            .and_then(|last| last.gpus.get(gpu_id))
+                            .map_or(String::new(), |gpu| {
+                                format!("{}% ({} ℃)", gpu.usage, gpu.temp)
+                            })
+                    })
                 };
                 view! {
                     <button on:click=move |_| { main_view.set(MainView::Gpu(gpu_id)) }>
@@ -318,11 +504,10 @@ fn PlotDiskMini(
 ) -> impl IntoView {
     let div_id = "side-disk";
     Effect::new(move |_| {
-        let mut plot = plot_disk(&sys_util_history.get(), max_history.get());
+        let history = single_rollups(&sys_util_history.get());
+        let mut plot = plot_disk(&history, RAW_RESOLUTION);
 
-        let x_axis = Axis::new()
-            .range(AxisRange::new(0, max_history.get() - 1))
-            .tick_values(vec![]);
+        let x_axis = mini_axis(&history, max_history.get());
         let margin = Margin::new().left(0).right(0).top(0).bottom(0);
         let layout = plot.layout().clone().margin(margin).x_axis(x_axis);
         plot.set_layout(layout);
@@ -335,6 +520,68 @@ fn PlotDiskMini(
     view! { <div class="leftmini" id=div_id></div> }
 }
 
+#[component]
+fn PlotDisksMini(
+    sys_util_history: Signal<Vec<SystemUtilization>>,
+    max_history: ReadSignal<usize>,
+    main_view: WriteSignal<MainView>,
+) -> impl IntoView {
+    view! {
+        <For
+            each=move || {
+                sys_util_history
+                    .get()
+                    .last()
+                    .map_or(Vec::new(), |sys_util| {
+                        sys_util.disks.iter().map(|disk| disk.name.clone()).collect()
+                    })
+            }
+            key=|name| name.clone()
+            children=move |name| {
+                let div_id = format!("side-disk-{}", name);
+                {
+                    let div_id = div_id.clone();
+                    let name = name.clone();
+                    Effect::new(move |_| {
+                        let history = single_rollups(&sys_util_history.get());
+                        let mut plot = plot_disk_device(&history, RAW_RESOLUTION, &name);
+                        let x_axis = mini_axis(&history, max_history.get());
+                        let margin = Margin::new().left(0).right(0).top(0).bottom(0);
+                        let layout = plot.layout().clone().margin(margin).x_axis(x_axis);
+                        plot.set_layout(layout);
+                        let div_id = div_id.clone();
+                        spawn_local(async move {
+                            react(&div_id, &plot).await;
+                        });
+                    });
+                }
+                let disk_descr = {
+                    let name = name.clone();
+                    move || {
+                        sys_util_history
+                            .get()
+                            .last()
+                            .and_then(|sys_util| find_disk(sys_util, &name).cloned())
+                            .map_or(String::new(), |disk| format!("{:.0}% active", disk.busy))
+                    }
+                };
+                view! {
+                    <button on:click={
+                        let name = name.clone();
+                        move |_| { main_view.set(MainView::DiskDevice(name.clone())) }
+                    }>
+                        <div class="leftmini" id=div_id></div>
+                        <div class="rightmini">
+                            <div class="rightminititle">{format!("Disk {}", name)}</div>
+                            {disk_descr}
+                        </div>
+                    </button>
+                }
+            }
+        />
+    }
+}
+
 #[component]
 fn PlotNetworkMini(
     sys_util_history: Signal<Vec<SystemUtilization>>,
@@ -342,11 +589,10 @@ fn PlotNetworkMini(
 ) -> impl IntoView {
     let div_id = "side-network";
     Effect::new(move |_| {
-        let mut plot = plot_network(&sys_util_history.get(), max_history.get());
+        let history = single_rollups(&sys_util_history.get());
+        let mut plot = plot_network(&history, RAW_RESOLUTION, None);
 
-        let x_axis = Axis::new()
-            .range(AxisRange::new(0, max_history.get() - 1))
-            .tick_values(vec![]);
+        let x_axis = mini_axis(&history, max_history.get());
         let margin = Margin::new().left(0).right(0).top(0).bottom(0);
         let layout = plot.layout().clone().margin(margin).x_axis(x_axis);
         plot.set_layout(layout);
@@ -367,16 +613,17 @@ fn SidePanel(
 ) -> impl IntoView {
     let cpu_descr = move || {
         let sys_util_history = sys_util_history.get();
-        let (usage, freq) = if let Some(sys_util) = sys_util_history.last() {
+        let (usage, freq, temp) = if let Some(sys_util) = sys_util_history.last() {
             let cpus = &sys_util.cpus;
             let usage = cpus.iter().map(|cpu| cpu.usage).sum::<f32>() / cpus.len() as f32;
             let freq_mhz = cpus.iter().map(|cpu| cpu.freq).sum::<u64>() / cpus.len() as u64;
             let freq = freq_mhz as f32 / 1000.0;
-            (usage, freq)
+            (usage, freq, sys_util.cpu_temp())
         } else {
-            (0.0, 0.0)
+            (0.0, 0.0, None)
         };
-        format!("{:.0}% {:.2} GHz", usage, freq)
+        let temp = temp.map_or(String::new(), |temp| format!(" {:.0} °C", temp));
+        format!("{:.0}% {:.2} GHz{}", usage, freq, temp)
     };
 
     let mem_descr = move || {
@@ -414,6 +661,27 @@ fn SidePanel(
         (print_bytes(network.down), print_bytes(network.up))
     };
 
+    let sensors_descr = move || {
+        sys_util_history
+            .get()
+            .last()
+            .and_then(|sys_util| {
+                sys_util
+                    .sensors
+                    .iter()
+                    .map(|sensor| sensor.temp)
+                    .max_by(f32::total_cmp)
+            })
+            .map_or(String::new(), |temp| format!("Hottest {:.0} °C", temp))
+    };
+
+    let processes_descr = move || {
+        sys_util_history
+            .get()
+            .last()
+            .map_or(0, |sys_util| sys_util.processes)
+    };
+
     view! {
         <div>
             <button on:click=move |_| { main_view.set(MainView::Cpu) }>
@@ -466,6 +734,15 @@ fn SidePanel(
                 </div>
             </button>
 
+            <PlotDisksMini sys_util_history=sys_util_history max_history=max_history main_view/>
+
+            <button on:click=move |_| { main_view.set(MainView::Processes) }>
+                <div class="rightmini">
+                    <div class="rightminititle">Processes</div>
+                    {processes_descr}
+                </div>
+            </button>
+
             <button on:click=move |_| { main_view.set(MainView::Network) }>
                 <PlotNetworkMini sys_util_history=sys_util_history max_history=max_history/>
                 <div class="rightmini">
@@ -483,35 +760,18 @@ fn SidePanel(
                 </div>
             </button>
 
+            <button on:click=move |_| { main_view.set(MainView::Sensors) }>
+                <div class="rightmini">
+                    <div class="rightminititle">Sensors</div>
+                    {sensors_descr}
+                </div>
+            </button>
+
         // <img src="public/rzulta.png" style="width:100%; height:auto"/>
         </div>
     }
 }
 
-fn print_bytes(value: u64) -> String {
-    let mut value = value as f32;
-    let suffixes = ["B", "KiB", "MiB", "GiB", "TiB"];
-    let base = 1024.0;
-    let mut pow = 0;
-    while (value >= base) && pow < suffixes.len() - 1 {
-        value /= base;
-        pow += 1;
-    }
-    format!("{:.1} {}", value, suffixes.get(pow).unwrap())
-}
-
-fn print_secs(value: u64) -> String {
-    let mut value = value;
-    let suffixes = ["s", "min", "h"];
-    let base = 60;
-    let mut pow = 0;
-    while (value >= base) && pow < suffixes.len() - 1 {
-        value /= base;
-        pow += 1;
-    }
-    format!("{} {}", value, suffixes.get(pow).unwrap())
-}
-
 #[component]
 fn MainPanel(
     main_view: ReadSignal<MainView>,
@@ -519,86 +779,61 @@ fn MainPanel(
     sys_util_history: ReadSignal<VecDeque<SystemUtilization>>,
     max_history: ReadSignal<usize>,
     history_time: ReadSignal<usize>,
+    /// Whether a recording is replayed, whose processes weren't recorded
+    replaying: Signal<bool>,
 ) -> impl IntoView {
     let div_id = "main-view";
-
-    let sys_util_history_sampled = Signal::derive({
-        move || {
-            let history_time = history_time.get();
-            let sys_util_history = sys_util_history.get();
-            let step = history_time.div_ceil(max_history.get());
-
-            if sys_util_history.len() < step {
-                // So that there are proper y axes values for long periods such as 24h
-                sys_util_history.iter().take(1).cloned().collect()
-            } else {
-                sys_util_history
-                    .iter()
-                    .rev()
-                    .skip(sys_util_history.len() % step)
-                    .take(history_time)
-                    .collect::<Vec<_>>()
-                    .chunks(step)
-                    .map(|chunk| {
-                        let mut zero = SystemUtilization {
-                            cpus: vec![
-                                CpuCore::default();
-                                chunk.first().map_or(0, |util| util.cpus.len())
-                            ],
-                            gpus: vec![
-                                Gpu::default();
-                                chunk.first().map_or(0, |util| util.gpus.len())
-                            ],
-                            ..Default::default()
-                        };
-                        vec![Gpu::default(); chunk.first().map_or(0, |util| util.gpus.len())];
-                        for &el in chunk {
-                            zero = zero + el.clone();
-                        }
-                        zero.cpus = zero
-                            .cpus
-                            .iter()
-                            .map(|cpu| CpuCore {
-                                usage: cpu.usage / step as f32,
-                                freq: cpu.freq / step as u64,
-                            })
-                            .collect();
-                        zero.gpus = zero
-                            .gpus
-                            .iter()
-                            .map(|gpu| Gpu {
-                                usage: gpu.usage / step as u32,
-                                mem: gpu.mem / step as u32,
-                                max_mem: gpu.max_mem / step as u64,
-                                temp: gpu.temp / step as u32,
-                            })
-                            .collect();
-                        zero.mem /= step as u64;
-                        zero.mem_max /= step as u64;
-                        zero.disk /= step as u64;
-                        zero.up_time /= step as u32;
-                        zero.network /= step as u64;
-                        zero.processes /= step as u32;
-
-                        zero
-                    })
-                    .rev()
-                    .collect::<Vec<_>>()
+    let selected_interface = RwSignal::new(None::<String>);
+    let show_kernel_times = RwSignal::new(false);
+    let show_logical_processors = RwSignal::new(false);
+
+    let sampled_history = RwSignal::new(Vec::new());
+    let sys_util_history_sampled: Signal<Vec<Rollup>> = sampled_history.into();
+    let latest_sampled =
+        Signal::derive(move || sys_util_history_sampled.with(|history| latest(history)));
+    // Length of a bucket in milliseconds, so that the period fits in `max_history` of them
+    let resolution = Signal::derive(move || {
+        let history_time = history_time.get() as u64 * 1000;
+        history_time.div_ceil(max_history.get() as u64 * 1000) * 1000
+    });
+    // Fetches the whole period again on every new sample. Responses may arrive out of order, so
+    // only the one to the latest request is kept.
+    let request = StoredValue::new(0_u64);
+    Effect::new(move |_| {
+        let Some(to) = sys_util_history.with(|history| history.back().map(|util| util.timestamp))
+        else {
+            return;
+        };
+        let history_time = history_time.get() as u64 * 1000;
+        let resolution = resolution.get();
+        let args = HistoryArgs {
+            // Buckets are centered on `to`, leave out the one half outside of the period
+            from: to.saturating_sub(history_time - resolution / 2),
+            to,
+            resolution,
+        };
+        let args = serde_wasm_bindgen::to_value(&args).unwrap();
+        request.update_value(|request| *request += 1);
+        let this_request = request.get_value();
+        spawn_local(async move {
+            let values = invoke("get_history", args).await;
+            if request.get_value() == this_request {
+                sampled_history.set(serde_wasm_bindgen::from_value(values).unwrap());
             }
-        }
+        });
     });
 
     Effect::new(move |_| {
-        let binding = sys_util_history.get();
-        let sys_util_history = binding.iter().rev().take(history_time.get());
+        // Redrawn once the history for the new sample arrives
+        let binding = sys_util_history.get_untracked();
         let sys_util_history_sampled = sys_util_history_sampled.get();
+        let resolution = resolution.get();
+        // Every sample averages this many seconds
+        let step = resolution / 1000;
 
         let mut title = Title::from("");
         let black = Rgb::new(0, 0, 0);
-        let x_axis = Axis::new()
-            .range(AxisRange::new(0, max_history.get() - 1))
-            .tick_values(vec![0.0])
-            .tick_text(vec![format!("{}", print_secs(history_time.get() as u64))])
+        let x_axis = time_axis(latest_sampled.get(), history_time.get() as u64 * 1000)
             .line_color(black)
             .mirror(true);
         let y_ticks = vec![0.0, 20.0, 40.0, 60.0, 80.0, 100.0];
@@ -609,10 +844,27 @@ fn MainPanel(
             .mirror(true);
 
         let mut plot = match main_view.get() {
+            MainView::Processes => return,
+
+            MainView::Cpu if show_logical_processors.get() => return,
+
             MainView::Cpu => {
-                let plot = plot_cpu(&sys_util_history_sampled, max_history.get());
+                let mut plot = plot_cpu(&sys_util_history_sampled, resolution);
+                if show_kernel_times.get() {
+                    add_kernel_times(&mut plot, &sys_util_history_sampled, resolution);
+                }
 
-                title = Title::from(&sys_info.get().cpu_brand.to_string());
+                let times = binding.back().map_or(String::new(), |util| {
+                    let times = &util.cpu_times;
+                    format!(
+                        " | user {:.0}% | kernel {:.0}% | iowait {:.0}% | steal {:.0}%",
+                        times.user + times.nice,
+                        times.kernel(),
+                        times.iowait,
+                        times.steal
+                    )
+                });
+                title = Title::from(&format!("{}{}", sys_info.get().cpu_brand, times));
                 let y_ticks_text = y_ticks.iter().map(|x| format!("{:.0}%", x)).collect();
                 y_axis = y_axis
                     .range(AxisRange::new(0, 100))
@@ -623,11 +875,11 @@ fn MainPanel(
             }
 
             MainView::Mem => {
-                let plot = plot_mem(&sys_util_history_sampled, max_history.get());
+                let plot = plot_mem(&sys_util_history_sampled, resolution);
 
                 let mem_max = sys_util_history_sampled
                     .first()
-                    .map_or(0, |sys_util| sys_util.mem_max);
+                    .map_or(0, |rollup| rollup.avg.mem_max);
                 let y_ticks_values: Vec<_> =
                     y_ticks.iter().map(|y| y * mem_max as f64 / 100.0).collect();
                 let y_ticks_text = y_ticks_values
@@ -643,9 +895,40 @@ fn MainPanel(
             }
 
             MainView::Gpu(gpu_id) => {
-                let plot = plot_gpu(&sys_util_history_sampled, max_history.get(), gpu_id);
-
-                title = Title::from(&sys_info.get().gpu_names[gpu_id]);
+                let plot = plot_gpu(&sys_util_history_sampled, resolution, gpu_id);
+
+                let details = binding
+                    .back()
+                    .and_then(|util| util.gpus.get(gpu_id))
+                    .map_or(String::new(), |gpu| {
+                        let fan = gpu
+                            .fan
+                            .map_or(String::new(), |fan| format!(" | fan {}%", fan));
+                        let throttle = if gpu.throttle_reasons.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" | throttled: {}", gpu.throttle_reasons.join(", "))
+                        };
+                        format!(
+                            " | {} / {} MHz{} | PCIe {}/s | {}/s | enc {}% dec {}%{}",
+                            gpu.graphics_clock,
+                            gpu.memory_clock,
+                            fan,
+                            print_bytes(gpu.pcie_rx),
+                            print_bytes(gpu.pcie_tx),
+                            gpu.encoder,
+                            gpu.decoder,
+                            throttle
+                        )
+                    });
+                // A replayed recording can have GPUs the system doesn't
+                let name = sys_info
+                    .get()
+                    .gpu_names
+                    .get(gpu_id)
+                    .cloned()
+                    .unwrap_or_else(|| format!("GPU {gpu_id}"));
+                title = Title::from(&format!("{}{}", name, details));
                 let y_ticks_text = y_ticks.iter().map(|x| format!("{:.0}%", x)).collect();
                 y_axis = y_axis
                     .range(AxisRange::new(0, 100))
@@ -656,10 +939,15 @@ fn MainPanel(
             }
 
             MainView::Network => {
-                let plot = plot_network(&sys_util_history_sampled, max_history.get());
+                let interface = selected_interface.get();
+                let interface = interface.as_deref();
+                let plot = plot_network(&sys_util_history_sampled, resolution, interface);
                 let max = sys_util_history_sampled
                     .iter()
-                    .map(|util| util.network.down.max(util.network.up))
+                    .map(|rollup| {
+                        let (down, up) = network_speed(&rollup.max, interface);
+                        down.max(up)
+                    })
                     .max()
                     .unwrap_or(0);
                 let y_ticks_values: Vec<_> =
@@ -673,22 +961,67 @@ fn MainPanel(
                     .tick_values(y_ticks_values)
                     .tick_text(y_ticks_text);
 
-                let (total_down, total_up) = sys_util_history.fold((0, 0), |(down, up), util| {
-                    (down + util.network.down, up + util.network.up)
-                });
+                let (total_down, total_up) =
+                    sys_util_history_sampled
+                        .iter()
+                        .fold((0, 0), |(down, up), rollup| {
+                            let (util_down, util_up) = network_speed(&rollup.avg, interface);
+                            (down + util_down * step, up + util_up * step)
+                        });
+                let details = binding
+                    .back()
+                    .and_then(|util| {
+                        let name = interface?;
+                        util.network
+                            .interfaces
+                            .iter()
+                            .find(|interface| interface.name == name)
+                    })
+                    .map_or(String::new(), |interface| {
+                        format!(
+                            "{} {} | {} errors, {} dropped | ",
+                            interface.mac,
+                            interface.ips.join(" "),
+                            interface.errors_down + interface.errors_up,
+                            interface.drops_down + interface.drops_up
+                        )
+                    });
                 title = Title::from(&format!(
-                    "Total: {} | {}",
+                    "{}Total: {} | {}",
+                    details,
                     print_bytes(total_down),
                     print_bytes(total_up)
                 ));
                 plot
             }
 
+            MainView::Sensors => {
+                let plot = plot_sensors(&sys_util_history_sampled, resolution);
+                let max = sys_util_history_sampled
+                    .iter()
+                    .flat_map(|rollup| &rollup.max.sensors)
+                    .map(|sensor| sensor.temp)
+                    .fold(100.0_f32, f32::max);
+                let max = (max / 10.0).ceil() * 10.0;
+                let y_ticks_values: Vec<_> =
+                    y_ticks.iter().map(|y| y * max as f64 / 100.0).collect();
+                let y_ticks_text = y_ticks_values
+                    .iter()
+                    .map(|y| format!("{:.0} °C", y))
+                    .collect();
+                y_axis = y_axis
+                    .range(AxisRange::new(0.0, max as f64))
+                    .tick_values(y_ticks_values)
+                    .tick_text(y_ticks_text);
+
+                plot
+            }
+
             MainView::Disk => {
-                let plot = plot_disk(&sys_util_history_sampled, max_history.get());
+                let plot = plot_disk(&sys_util_history_sampled, resolution);
                 let max = sys_util_history_sampled
                     .iter()
-                    .map(|util| util.disk.read_bytes.max(util.disk.writen_bytes))
+                    .map(|rollup| rollup.max.disk.read_bytes.max(rollup.max.disk.writen_bytes))
                     .max()
                     .unwrap_or(0);
                 let y_ticks_values: Vec<_> =
@@ -703,9 +1036,14 @@ fn MainPanel(
                     .tick_text(y_ticks_text);
 
                 let (total_read, total_write) =
-                    sys_util_history.fold((0, 0), |(read, write), util| {
-                        (read + util.disk.read_bytes, write + util.disk.writen_bytes)
-                    });
+                    sys_util_history_sampled
+                        .iter()
+                        .fold((0, 0), |(read, write), rollup| {
+                            (
+                                read + rollup.avg.disk.read_bytes * step,
+                                write + rollup.avg.disk.writen_bytes * step,
+                            )
+                        });
                 title = Title::from(&format!(
                     "Total: {} | {}",
                     print_bytes(total_read),
@@ -714,6 +1052,35 @@ fn MainPanel(
 
                 plot
             }
+
+            MainView::DiskDevice(name) => {
+                let plot = plot_disk_device(&sys_util_history_sampled, resolution, &name);
+                let max = sys_util_history_sampled
+                    .iter()
+                    .filter_map(|rollup| find_disk(&rollup.max, &name))
+                    .map(|disk| disk.read_bytes.max(disk.written_bytes))
+                    .max()
+                    .unwrap_or(0);
+                let y_ticks_values: Vec<_> =
+                    y_ticks.iter().map(|y| y * max as f64 / 100.0).collect();
+                let y_ticks_text = y_ticks_values
+                    .iter()
+                    .map(|y| format!("{}/s", print_bytes(*y as u64)))
+                    .collect();
+                y_axis = y_axis
+                    .range(AxisRange::new(0, max))
+                    .tick_values(y_ticks_values)
+                    .tick_text(y_ticks_text);
+
+                if let Some(disk) = binding.back().and_then(|util| find_disk(util, &name)) {
+                    title = Title::from(&format!(
+                        "{} | {:.0}% active | {:.0} IOPS | {:.1} ms",
+                        name, disk.busy, disk.iops, disk.latency
+                    ));
+                }
+
+                plot
+            }
         };
 
         let _transparent = Rgba::new(0, 0, 0, 0.0);
@@ -731,57 +1098,1012 @@ fn MainPanel(
         });
     });
 
+    let is_processes = move || matches!(main_view.get(), MainView::Processes);
+    let is_cpu_grid =
+        move || matches!(main_view.get(), MainView::Cpu) && show_logical_processors.get();
+
+    let interface_names = move || {
+        sys_util_history.with(|history| {
+            history.back().map_or(Vec::new(), |util| {
+                util.network
+                    .interfaces
+                    .iter()
+                    .map(|interface| (interface.name.clone(), interface.included))
+                    .collect()
+            })
+        })
+    };
+
     view! {
         <div class="rightpanel">
-            <div style="height:450px">
-                <div id=div_id></div>
+            <Show when=move || matches!(main_view.get(), MainView::Cpu)>
+                <label>
+                    <input
+                        type="checkbox"
+                        prop:checked=show_kernel_times
+                        on:change=move |ev| show_kernel_times.set(event_target_checked(&ev))
+                    />
+                    "Show kernel times"
+                </label>
+                <label>
+                    <input
+                        type="checkbox"
+                        prop:checked=show_logical_processors
+                        on:change=move |ev| show_logical_processors.set(event_target_checked(&ev))
+                    />
+                    "Logical processors"
+                </label>
+            </Show>
+            <Show when=move || matches!(main_view.get(), MainView::Network)>
+                <select on:change=move |ev| {
+                    let name = event_target_value(&ev);
+                    selected_interface.set((!name.is_empty()).then_some(name));
+                }>
+                    <option value="">"Total"</option>
+                    {move || {
+                        interface_names()
+                            .into_iter()
+                            .map(|(name, included)| {
+                                let selected = selected_interface.get().as_ref() == Some(&name);
+                                let label = if included {
+                                    name.clone()
+                                } else {
+                                    format!("{} (not in total)", name)
+                                };
+                                view! {
+                                    <option value=name selected=selected>
+                                        {label}
+                                    </option>
+                                }
+                            })
+                            .collect_view()
+                    }}
+                </select>
+            </Show>
+            <div style:display=move || {
+                if is_processes() || is_cpu_grid() { "none" } else { "block" }
+            }>
+                <div style="height:450px">
+                    <div id=div_id></div>
+                </div>
             </div>
+            <Show when=is_cpu_grid>
+                <CpuGrid
+                    sys_util_history=sys_util_history_sampled
+                    resolution=resolution
+                    history_time=history_time
+                />
+            </Show>
+            <Show when=is_processes>
+                // The processes running now, which could be killed from the table, aren't the
+                // recorded ones
+                <Show
+                    when=move || !replaying.get()
+                    fallback=|| view! { <p>"Processes aren't part of recordings"</p> }
+                >
+                    <ProcessTable sys_util_history=sys_util_history/>
+                </Show>
+            </Show>
+            <Show when=move || matches!(main_view.get(), MainView::Mem)>
+                <MemoryComposition sys_util_history=sys_util_history/>
+                <PlotDetail
+                    div_id="main-view-swap"
+                    title="Swap"
+                    values=Signal::derive(move || {
+                        sys_util_history_sampled.with(|history| {
+                            let values = series(history, resolution.get(), |util| {
+                                util.memory.swap_used as f64
+                            });
+                            let max = history
+                                .first()
+                                .map_or(0.0, |rollup| rollup.avg.memory.swap_total as f64);
+                            (values, max)
+                        })
+                    })
+                    color=Rgb::new(190, 39, 101)
+                    print_value=|value| print_bytes(value as u64)
+                    to=latest_sampled
+                    history_time=history_time
+                />
+            </Show>
+            <Show when=move || matches!(main_view.get(), MainView::Disk)>
+                <FilesystemTable sys_util_history=sys_util_history/>
+            </Show>
+            {move || match main_view.get() {
+                MainView::Gpu(gpu_id) => {
+                    let gpu_values = move |value: fn(&Gpu) -> f64, max: fn(&Gpu) -> f64| {
+                        sys_util_history_sampled.with(|history| {
+                            let values = series(history, resolution.get(), |util| {
+                                util.gpus.get(gpu_id).map_or(0.0, value)
+                            });
+                            let max = history
+                                .iter()
+                                .filter_map(|rollup| rollup.max.gpus.get(gpu_id))
+                                .map(max)
+                                .fold(0.0, f64::max);
+                            (values, max)
+                        })
+                    };
+                    Some(
+                        view! {
+                            <PlotDetail
+                                div_id="main-view-vram"
+                                title="Video memory"
+                                values=Signal::derive(move || {
+                                    gpu_values(
+                                        |gpu| gpu.vram_used as f64,
+                                        |gpu| gpu.vram_total as f64,
+                                    )
+                                })
+                                color=Rgb::new(120, 149, 203)
+                                print_value=|value| print_bytes(value as u64)
+                                to=latest_sampled
+                                history_time=history_time
+                            />
+                            <PlotDetail
+                                div_id="main-view-power"
+                                title="Power"
+                                values=Signal::derive(move || {
+                                    gpu_values(
+                                        |gpu| gpu.power as f64,
+                                        |gpu| gpu.power_limit.max(gpu.power) as f64,
+                                    )
+                                })
+                                color=Rgb::new(255, 127, 14)
+                                print_value=|value| format!("{:.0} W", value)
+                                to=latest_sampled
+                                history_time=history_time
+                            />
+                            <Show when=move || !replaying.get()>
+                                <GpuProcessTable
+                                    gpu_id=gpu_id
+                                    sys_info=sys_info
+                                    sys_util_history=sys_util_history
+                                />
+                            </Show>
+                        },
+                    )
+                }
+                _ => None,
+            }}
+            <Show when=move || matches!(main_view.get(), MainView::Sensors)>
+                <SensorTable sys_util_history=sys_util_history/>
+            </Show>
+        </div>
+    }
+}
+
+/// One graph per logical processor, like Task Manager's "Logical processors" mode
+#[component]
+fn CpuGrid(
+    sys_util_history: Signal<Vec<Rollup>>,
+    /// Length of a bucket in milliseconds
+    resolution: Signal<u64>,
+    history_time: ReadSignal<usize>,
+) -> impl IntoView {
+    let core_count = move || {
+        sys_util_history.with(|history| history.last().map_or(0, |rollup| rollup.avg.cpus.len()))
+    };
+
+    view! {
+        <div class="cpu-grid">
+            <For
+                each=move || 0..core_count()
+                key=|core| *core
+                children=move |core| {
+                    let div_id = format!("cpu-grid-{}", core);
+                    {
+                        let div_id = div_id.clone();
+                        Effect::new(move |_| {
+                            let history = sys_util_history.get();
+                            let mut plot = plot_cpu_core(&history, resolution.get(), core);
+                            let y_axis = Axis::new()
+                                .range(AxisRange::new(0, 100))
+                                .tick_values(vec![]);
+                            let period = history_time.get() as u64 * 1000;
+                            let x_axis = time_axis(latest(&history), period).tick_values(vec![]);
+                            let margin = Margin::new().left(0).right(0).top(0).bottom(0);
+                            let layout = plot
+                                .layout()
+                                .clone()
+                                .margin(margin)
+                                .y_axis(y_axis)
+                                .x_axis(x_axis);
+                            plot.set_layout(layout);
+                            let div_id = div_id.clone();
+                            spawn_local(async move {
+                                react(&div_id, &plot).await;
+                            });
+                        });
+                    }
+                    let core_descr = move || {
+                        sys_util_history
+                            .get()
+                            .last()
+                            .and_then(|rollup| rollup.avg.cpus.get(core).cloned())
+                            .map_or(String::new(), |cpu| {
+                                format!("{:.0}% {:.2} GHz", cpu.usage, cpu.freq as f32 / 1000.0)
+                            })
+                    };
+                    view! {
+                        <div class="cpu-grid-cell">
+                            <div class="cpu-grid-title">
+                                {format!("CPU {}", core)}
+                                <span>{core_descr}</span>
+                            </div>
+                            <div class="cpu-grid-plot" id=div_id></div>
+                        </div>
+                    }
+                }
+            />
         </div>
     }
 }
 
+/// Bar split into memory in use, reclaimable cache and buffers, and free memory
+#[component]
+fn MemoryComposition(sys_util_history: ReadSignal<VecDeque<SystemUtilization>>) -> impl IntoView {
+    let latest = move || sys_util_history.with(|history| history.back().cloned());
+
+    view! {
+        {move || {
+            latest()
+                .map(|util| {
+                    let memory = util.memory;
+                    let total = util.mem_max.max(1);
+                    let in_use = total.saturating_sub(memory.available);
+                    let cached = memory.available.saturating_sub(memory.free);
+                    let percent = |value: u64| {
+                        format!("width: {:.1}%", value as f64 / total as f64 * 100.0)
+                    };
+                    view! {
+                        <div class="memory-composition">
+                            <div class="memory-bar">
+                                <div class="in-use" style=percent(in_use)></div>
+                                <div class="cached" style=percent(cached)></div>
+                                <div class="free" style=percent(memory.free)></div>
+                            </div>
+                            <table>
+                                <tr>
+                                    <td>"In use"</td>
+                                    <td>{print_bytes(in_use)}</td>
+                                    <td>"Available"</td>
+                                    <td>{print_bytes(memory.available)}</td>
+                                </tr>
+                                <tr>
+                                    <td>"Cached"</td>
+                                    <td>{print_bytes(memory.cached)}</td>
+                                    <td>"Buffers"</td>
+                                    <td>{print_bytes(memory.buffers)}</td>
+                                </tr>
+                                <tr>
+                                    <td>"Shared"</td>
+                                    <td>{print_bytes(memory.shared)}</td>
+                                    <td>"Free"</td>
+                                    <td>{print_bytes(memory.free)}</td>
+                                </tr>
+                                <tr>
+                                    <td>"Swap"</td>
+                                    <td>
+                                        {format!(
+                                            "{} of {}",
+                                            print_bytes(memory.swap_used),
+                                            print_bytes(memory.swap_total),
+                                        )}
+                                    </td>
+                                    <td>"Swap in/out"</td>
+                                    <td>
+                                        {format!(
+                                            "{}/s | {}/s",
+                                            print_bytes(memory.swap_in),
+                                            print_bytes(memory.swap_out),
+                                        )}
+                                    </td>
+                                </tr>
+                            </table>
+                        </div>
+                    }
+                })
+        }}
+    }
+}
+
+/// A smaller graph of a single value, shown below the main one
+#[component]
+fn PlotDetail(
+    div_id: &'static str,
+    title: &'static str,
+    /// Values over time and the top of the y axis
+    values: Signal<(Series<f64>, f64)>,
+    color: Rgb,
+    print_value: fn(f64) -> String,
+    /// When the newest sample was taken
+    to: Signal<u64>,
+    history_time: ReadSignal<usize>,
+) -> impl IntoView {
+    Effect::new(move |_| {
+        let (values, max) = values.get();
+        let mut plot = plot_generic_many(&[values], &[color], Fill::ToZeroY);
+
+        let black = Rgb::new(0, 0, 0);
+        let y_ticks_values: Vec<_> = [0.0, 25.0, 50.0, 75.0, 100.0]
+            .iter()
+            .map(|y| y * max / 100.0)
+            .collect();
+        let y_ticks_text = y_ticks_values.iter().map(|y| print_value(*y)).collect();
+        let y_axis = Axis::new()
+            .side(AxisSide::Right)
+            .line_color(black)
+            .mirror(true)
+            .range(AxisRange::new(0.0, max))
+            .tick_values(y_ticks_values)
+            .tick_text(y_ticks_text);
+        let x_axis = time_axis(to.get(), history_time.get() as u64 * 1000)
+            .line_color(black)
+            .mirror(true);
+        let layout = plot
+            .layout()
+            .clone()
+            .title(Title::from(title))
+            .y_axis(y_axis)
+            .x_axis(x_axis);
+        plot.set_layout(layout);
+
+        spawn_local(async move {
+            react(div_id, &plot).await;
+        });
+    });
+
+    view! {
+        <div style="height:250px">
+            <div id=div_id></div>
+        </div>
+    }
+}
+
+#[component]
+fn FilesystemTable(sys_util_history: ReadSignal<VecDeque<SystemUtilization>>) -> impl IntoView {
+    let filesystems = RwSignal::new(Vec::<Filesystem>::new());
+
+    Effect::new(move |_| {
+        sys_util_history.track();
+        spawn_local(async move {
+            let values = invoke("get_filesystems", JsValue::NULL).await;
+            let values = serde_wasm_bindgen::from_value(values).unwrap();
+            filesystems.set(values);
+        });
+    });
+
+    view! {
+        <table class="filesystems">
+            <tr>
+                <th>"Mount point"</th>
+                <th>"Type"</th>
+                <th>"Device"</th>
+                <th>"Used"</th>
+                <th></th>
+                <th>"Inodes used"</th>
+            </tr>
+            {move || {
+                filesystems
+                    .get()
+                    .into_iter()
+                    .map(|fs| {
+                        let used = fs.used as f32 / fs.total.max(1) as f32 * 100.0;
+                        let inodes = match (fs.inodes_used, fs.inodes_total) {
+                            (Some(used), Some(total)) => {
+                                format!("{:.0}%", used as f32 / total.max(1) as f32 * 100.0)
+                            }
+                            _ => "-".to_owned(),
+                        };
+                        view! {
+                            <tr>
+                                <td>{fs.mount_point}</td>
+                                <td>{fs.fs_type}</td>
+                                <td>{fs.device}</td>
+                                <td>
+                                    <div class="usage-bar">
+                                        <div style=format!("width: {:.0}%", used)></div>
+                                    </div>
+                                </td>
+                                <td>
+                                    {format!(
+                                        "{} free of {}",
+                                        print_bytes(fs.available),
+                                        print_bytes(fs.total),
+                                    )}
+                                </td>
+                                <td>{inodes}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()
+            }}
+        </table>
+    }
+}
+
+/// Number of processes listed in [`GpuProcessTable`]
+const TOP_GPU_PROCESSES: usize = 10;
+
+#[component]
+fn GpuProcessTable(
+    gpu_id: usize,
+    sys_info: ReadSignal<SystemInfo>,
+    sys_util_history: ReadSignal<VecDeque<SystemUtilization>>,
+) -> impl IntoView {
+    let processes = RwSignal::new(Vec::<GpuProcess>::new());
+
+    Effect::new(move |_| {
+        sys_util_history.track();
+        spawn_local(async move {
+            let values = invoke("get_gpu_processes", JsValue::NULL).await;
+            let values = serde_wasm_bindgen::from_value(values).unwrap();
+            processes.set(values);
+        });
+    });
+
+    let top_processes = move || {
+        let pci_slot = sys_info.with(|sys_info| {
+            sys_info
+                .gpu_pci_slots
+                .get(gpu_id)
+                .cloned()
+                .unwrap_or_default()
+        });
+        processes
+            .get()
+            .into_iter()
+            // Without a PCI slot the processes can't be told apart by GPU
+            .filter(|process| pci_slot.is_empty() || process.pci_slot == pci_slot)
+            .take(TOP_GPU_PROCESSES)
+            .collect::<Vec<_>>()
+    };
+
+    view! {
+        <table class="gpu-processes">
+            <tr>
+                <th>"Top GPU consumers"</th>
+                <th>"PID"</th>
+                <th>"GPU"</th>
+                <th>"Video memory"</th>
+            </tr>
+            {move || {
+                top_processes()
+                    .into_iter()
+                    .map(|process| {
+                        view! {
+                            <tr>
+                                <td>{process.name}</td>
+                                <td>{process.pid}</td>
+                                <td>{format!("{:.1}%", process.usage)}</td>
+                                <td>{print_bytes(process.vram)}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()
+            }}
+        </table>
+    }
+}
+
+#[component]
+fn SensorTable(sys_util_history: ReadSignal<VecDeque<SystemUtilization>>) -> impl IntoView {
+    let print_temp =
+        |temp: Option<f32>| temp.map_or("-".to_owned(), |temp| format!("{:.1} °C", temp));
+
+    view! {
+        <table class="sensors">
+            <tr>
+                <th></th>
+                <th>"Chip"</th>
+                <th>"Device"</th>
+                <th>"Sensor"</th>
+                <th>"Current"</th>
+                <th>"Max"</th>
+                <th>"Critical"</th>
+            </tr>
+            {move || {
+                sys_util_history
+                    .with(|history| {
+                        history.back().map_or(Vec::new(), |util| util.sensors.clone())
+                    })
+                    .into_iter()
+                    .enumerate()
+                    .map(|(id, sensor)| {
+                        let (r, g, b) = SENSOR_COLORS[id % SENSOR_COLORS.len()];
+                        let hot = sensor
+                            .max
+                            .or(sensor.critical)
+                            .is_some_and(|limit| sensor.temp >= limit);
+                        view! {
+                            <tr class:hot=hot>
+                                <td style=format!("color: rgb({}, {}, {})", r, g, b)>"■"</td>
+                                <td>{sensor.chip}</td>
+                                <td>{sensor.device}</td>
+                                <td>{sensor.label}</td>
+                                <td>{print_temp(Some(sensor.temp))}</td>
+                                <td>{print_temp(sensor.max)}</td>
+                                <td>{print_temp(sensor.critical)}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()
+            }}
+        </table>
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ProcessColumn {
+    Name,
+    Pid,
+    User,
+    State,
+    Cpu,
+    Mem,
+    DiskRead,
+    DiskWrite,
+}
+
+impl ProcessColumn {
+    const ALL: [ProcessColumn; 8] = [
+        ProcessColumn::Name,
+        ProcessColumn::Pid,
+        ProcessColumn::User,
+        ProcessColumn::State,
+        ProcessColumn::Cpu,
+        ProcessColumn::Mem,
+        ProcessColumn::DiskRead,
+        ProcessColumn::DiskWrite,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            ProcessColumn::Name => "Name",
+            ProcessColumn::Pid => "PID",
+            ProcessColumn::User => "User",
+            ProcessColumn::State => "State",
+            ProcessColumn::Cpu => "CPU",
+            ProcessColumn::Mem => "Memory",
+            ProcessColumn::DiskRead => "Disk read",
+            ProcessColumn::DiskWrite => "Disk write",
+        }
+    }
+
+    /// Usage columns compare whole subtrees, which for a flat list is just the process itself.
+    fn compare(self, a: &ProcessNode, b: &ProcessNode) -> std::cmp::Ordering {
+        let (proc_a, proc_b) = (&a.process, &b.process);
+        match self {
+            ProcessColumn::Name => proc_a.name.to_lowercase().cmp(&proc_b.name.to_lowercase()),
+            ProcessColumn::Pid => proc_a.pid.cmp(&proc_b.pid),
+            ProcessColumn::User => proc_a.user.cmp(&proc_b.user),
+            ProcessColumn::State => proc_a.state.cmp(&proc_b.state),
+            ProcessColumn::Cpu => a.subtree_cpu_usage.total_cmp(&b.subtree_cpu_usage),
+            ProcessColumn::Mem => a.subtree_mem.cmp(&b.subtree_mem),
+            ProcessColumn::DiskRead => a.subtree_disk_read.cmp(&b.subtree_disk_read),
+            ProcessColumn::DiskWrite => a.subtree_disk_write.cmp(&b.subtree_disk_write),
+        }
+    }
+}
+
+struct ProcessRow {
+    node: ProcessNode,
+    depth: usize,
+    has_children: bool,
+}
+
+fn flatten_process_tree(
+    mut nodes: Vec<ProcessNode>,
+    depth: usize,
+    expanded: &HashSet<u32>,
+    sort: &impl Fn(&mut [ProcessNode]),
+    rows: &mut Vec<ProcessRow>,
+) {
+    sort(&mut nodes);
+    for mut node in nodes {
+        let children = std::mem::take(&mut node.children);
+        let is_expanded = expanded.contains(&node.process.pid);
+        rows.push(ProcessRow {
+            node,
+            depth,
+            has_children: !children.is_empty(),
+        });
+        if is_expanded {
+            flatten_process_tree(children, depth + 1, expanded, sort, rows);
+        }
+    }
+}
+
+/// Own usage, followed by the subtree's total for processes with children
+fn print_usage(own: String, subtree: String, has_children: bool) -> String {
+    if has_children {
+        format!("{own} ({subtree})")
+    } else {
+        own
+    }
+}
+
+const PROCESS_ACTIONS: [(ProcessSignal, &str); 4] = [
+    (ProcessSignal::Terminate, "End task"),
+    (ProcessSignal::Kill, "Kill"),
+    (ProcessSignal::Suspend, "Suspend"),
+    (ProcessSignal::Resume, "Resume"),
+];
+
+#[derive(Serialize)]
+struct PidArgs {
+    pid: u32,
+}
+
+fn signal_process(pid: u32, name: &str, signal: ProcessSignal, action: &str) {
+    let message = format!("{action} {name} (PID {pid})?");
+    if !window().confirm_with_message(&message).unwrap_or(false) {
+        return;
+    }
+    let command = match signal {
+        ProcessSignal::Terminate => "terminate_process",
+        ProcessSignal::Kill => "kill_process",
+        ProcessSignal::Suspend => "suspend_process",
+        ProcessSignal::Resume => "resume_process",
+    };
+    spawn_local(async move {
+        let args = serde_wasm_bindgen::to_value(&PidArgs { pid }).unwrap();
+        if let Err(err) = try_invoke(command, args).await {
+            let reason = serde_wasm_bindgen::from_value::<ProcessActionError>(err)
+                .map_or("Unknown error".to_owned(), |err| err.to_string());
+            let _ = window().alert_with_message(&format!("PID {pid}: {reason}"));
+        }
+    });
+}
+
+#[derive(Clone)]
+struct ProcessMenu {
+    pid: u32,
+    name: String,
+    x: i32,
+    y: i32,
+}
+
+#[component]
+fn ProcessTable(sys_util_history: ReadSignal<VecDeque<SystemUtilization>>) -> impl IntoView {
+    let processes = RwSignal::new(Vec::<ProcessInfo>::new());
+    let sort_column = RwSignal::new(ProcessColumn::Cpu);
+    let sort_descending = RwSignal::new(true);
+    let tree_mode = RwSignal::new(false);
+    let expanded = RwSignal::new(HashSet::<u32>::new());
+    let menu = RwSignal::new(None::<ProcessMenu>);
+
+    // Refetch whenever a new sample arrives, the backend refreshes processes along with it
+    Effect::new(move |_| {
+        sys_util_history.track();
+        spawn_local(async move {
+            let values = invoke("get_processes", JsValue::NULL).await;
+            let values = serde_wasm_bindgen::from_value(values).unwrap();
+            processes.set(values);
+        });
+    });
+
+    let set_sort = move |column: ProcessColumn| {
+        if sort_column.get() == column {
+            sort_descending.update(|descending| *descending = !*descending);
+        } else {
+            sort_column.set(column);
+            sort_descending.set(true);
+        }
+    };
+
+    let rows = move || {
+        let processes = processes.get();
+        let column = sort_column.get();
+        let descending = sort_descending.get();
+        let sort = |nodes: &mut [ProcessNode]| {
+            nodes.sort_by(|a, b| column.compare(a, b));
+            if descending {
+                nodes.reverse();
+            }
+        };
+        let nodes = if tree_mode.get() {
+            process_tree(processes)
+        } else {
+            processes
+                .into_iter()
+                .map(|proc| ProcessNode::new(proc, Vec::new()))
+                .collect()
+        };
+        let mut rows = Vec::new();
+        expanded.with(|expanded| flatten_process_tree(nodes, 0, expanded, &sort, &mut rows));
+        rows
+    };
+
+    let toggle_expanded = move |pid: u32| {
+        expanded.update(|expanded| {
+            if !expanded.remove(&pid) {
+                expanded.insert(pid);
+            }
+        })
+    };
+
+    view! {
+        <label>
+            <input type="checkbox" on:change=move |ev| tree_mode.set(event_target_checked(&ev))/>
+            "Tree view"
+        </label>
+        <table class="processes">
+            <tr>
+                {ProcessColumn::ALL
+                    .into_iter()
+                    .map(|column| {
+                        let arrow = move || {
+                            if sort_column.get() != column {
+                                ""
+                            } else if sort_descending.get() {
+                                " ▼"
+                            } else {
+                                " ▲"
+                            }
+                        };
+                        view! {
+                            <th on:click=move |_| set_sort(column)>{column.title()}{arrow}</th>
+                        }
+                    })
+                    .collect_view()}
+            </tr>
+            {move || {
+                rows()
+                    .into_iter()
+                    .map(|ProcessRow { node, depth, has_children }| {
+                        let proc = node.process;
+                        let (pid, name) = (proc.pid, proc.name.clone());
+                        let is_expanded = expanded.with_untracked(|pids| pids.contains(&pid));
+                        let open_menu = move |ev: leptos::ev::MouseEvent| {
+                            ev.prevent_default();
+                            menu.set(Some(ProcessMenu {
+                                pid,
+                                name: name.clone(),
+                                x: ev.client_x(),
+                                y: ev.client_y(),
+                            }));
+                        };
+                        view! {
+                            <tr title=proc.cmd on:contextmenu=open_menu>
+                                <td style=format!("padding-left: {}px", 4 + depth * 16)>
+                                    <span class="expander" on:click=move |_| toggle_expanded(pid)>
+                                        {if !has_children {
+                                            ""
+                                        } else if is_expanded {
+                                            "▼ "
+                                        } else {
+                                            "▶ "
+                                        }}
+                                    </span>
+                                    {proc.name}
+                                </td>
+                                <td>{proc.pid}</td>
+                                <td>{proc.user}</td>
+                                <td>{proc.state}</td>
+                                <td>
+                                    {print_usage(
+                                        format!("{:.1}%", proc.cpu_usage),
+                                        format!("{:.1}%", node.subtree_cpu_usage),
+                                        has_children,
+                                    )}
+                                </td>
+                                <td>
+                                    {print_usage(
+                                        print_bytes(proc.mem),
+                                        print_bytes(node.subtree_mem),
+                                        has_children,
+                                    )}
+                                </td>
+                                <td>
+                                    {print_usage(
+                                        format!("{}/s", print_bytes(proc.disk_read)),
+                                        format!("{}/s", print_bytes(node.subtree_disk_read)),
+                                        has_children,
+                                    )}
+                                </td>
+                                <td>
+                                    {print_usage(
+                                        format!("{}/s", print_bytes(proc.disk_write)),
+                                        format!("{}/s", print_bytes(node.subtree_disk_write)),
+                                        has_children,
+                                    )}
+                                </td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()
+            }}
+        </table>
+        {move || {
+            menu.get()
+                .map(|ProcessMenu { pid, name, x, y }| {
+                    let close = move |ev: leptos::ev::MouseEvent| {
+                        ev.prevent_default();
+                        menu.set(None);
+                    };
+                    view! {
+                        <div class="context-menu-overlay" on:click=close on:contextmenu=close></div>
+                        <div class="context-menu" style=format!("left: {x}px; top: {y}px")>
+                            {PROCESS_ACTIONS
+                                .into_iter()
+                                .map(|(signal, action)| {
+                                    let name = name.clone();
+                                    view! {
+                                        <button on:click=move |_| {
+                                            menu.set(None);
+                                            signal_process(pid, &name, signal, action);
+                                        }>{action}</button>
+                                    }
+                                })
+                                .collect_view()}
+                        </div>
+                    }
+                })
+        }}
+    }
+}
+
 #[derive(Clone)]
 enum MainView {
     Cpu,
     Mem,
     Gpu(usize),
     Disk,
+    DiskDevice(String),
     Network,
+    Processes,
+    Sensors,
 }
 
-const TIME_OPTIONS: [u64; 7] = [
-    60,
-    5 * 60,
-    30 * 60,
-    3 * 3600,
-    6 * 3600,
-    12 * 3600,
-    24 * 3600,
-];
+/// Saves the period shown to the downloads directory
+#[component]
+fn ExportHistory(
+    sys_util_history: ReadSignal<VecDeque<SystemUtilization>>,
+    history_time: ReadSignal<usize>,
+) -> impl IntoView {
+    let format = RwSignal::new("csv".to_owned());
+    let export = move |_| {
+        let Some(to) = sys_util_history.with(|history| history.back().map(|util| util.timestamp))
+        else {
+            return;
+        };
+        let args = ExportArgs {
+            from: to.saturating_sub(history_time.get() as u64 * 1000),
+            to,
+            format: format.get(),
+        };
+        let args = serde_wasm_bindgen::to_value(&args).unwrap();
+        spawn_local(async move {
+            let message = match try_invoke("export_history", args).await {
+                Ok(path) => format!("Exported to {}", path.as_string().unwrap_or_default()),
+                Err(err) => format!("Export failed: {}", err.as_string().unwrap_or_default()),
+            };
+            let _ = window().alert_with_message(&message);
+        });
+    };
 
-fn push_stats(curr: WriteSignal<VecDeque<SystemUtilization>>, interval: Duration) {
-    let t0 = js_sys::Date::now();
-    spawn_local(async move {
-        let values = invoke("get_stats", JsValue::NULL).await;
-        let values: SystemUtilization = serde_wasm_bindgen::from_value(values).unwrap();
+    view! {
+        <div style="margin-top:10px">
+            <b>"Export: "</b>
+            <select on:change=move |ev| format.set(event_target_value(&ev))>
+                <option value="csv">"CSV"</option>
+                <option value="jsonl">"JSON Lines"</option>
+                <option value="parquet">"Parquet"</option>
+            </select>
+            <button on:click=export>"Save"</button>
+        </div>
+    }
+}
+
+/// How many times faster than real time a recording can be replayed
+const REPLAY_SPEEDS: [u64; 5] = [1, 2, 5, 10, 60];
+
+fn print_clock_time(timestamp: u64) -> String {
+    js_sys::Date::new(&JsValue::from_f64(timestamp as f64))
+        .to_locale_string("default", &JsValue::UNDEFINED)
+        .into()
+}
+
+/// Replays a recording by feeding the panels the samples up to the scrubber's position,
+/// as if they had just been taken
+#[component]
+fn ReplayControls(
+    /// When the first and the last sample were taken
+    range: (u64, u64),
+    sys_util_history: WriteSignal<VecDeque<SystemUtilization>>,
+) -> impl IntoView {
+    let (start, end) = range;
+    let position = RwSignal::new(start);
+    let playing = RwSignal::new(false);
+    let speed = RwSignal::new(REPLAY_SPEEDS[0]);
+
+    Effect::new(move |_| {
+        let to = position.get();
+        let args = RecordingArgs {
+            from: to.saturating_sub(TIME_OPTIONS[0] * 1000),
+            to,
+        };
+        let args = serde_wasm_bindgen::to_value(&args).unwrap();
+        spawn_local(async move {
+            let values = invoke("get_recording", args).await;
+            sys_util_history.set(serde_wasm_bindgen::from_value(values).unwrap());
+        });
+    });
+
+    // The controls stay for as long as the app runs, and so does the timer
+    set_interval(
+        move || {
+            if !playing.get_untracked() {
+                return;
+            }
+            let next = position.get_untracked() + speed.get_untracked() * RAW_RESOLUTION;
+            if next >= end {
+                playing.set(false);
+            }
+            position.set(next.min(end));
+        },
+        SAMPLE_INTERVAL,
+    );
+    let play_pause = move |_| {
+        if !playing.get() && position.get() == end {
+            position.set(start);
+        }
+        playing.update(|playing| *playing = !*playing);
+    };
+
+    view! {
+        <div style="margin-top:10px">
+            <b>"Replay: "</b>
+            {move || print_clock_time(position.get())}
+            <input
+                type="range"
+                style="width:100%"
+                min=start
+                max=end
+                step=RAW_RESOLUTION
+                prop:value=move || position.get().to_string()
+                on:input=move |ev| {
+                    position.set(event_target_value(&ev).parse().unwrap_or(start))
+                }
+            />
+            <button on:click=play_pause>
+                {move || if playing.get() { "Pause" } else { "Play" }}
+            </button>
+            <select on:change=move |ev| {
+                speed.set(event_target_value(&ev).parse().unwrap_or(REPLAY_SPEEDS[0]))
+            }>
+                {REPLAY_SPEEDS
+                    .into_iter()
+                    .map(|speed| view! { <option value=speed>{format!("{speed}×")}</option> })
+                    .collect_view()}
+            </select>
+        </div>
+    }
+}
+
+/// Appends every sample the backend emits, keeping as many as the side panel shows.
+/// Longer periods are fetched from the backend's history.
+fn subscribe_stats(curr: WriteSignal<VecDeque<SystemUtilization>>) {
+    let handler = Closure::new(move |event: JsValue| {
+        let event: TauriEvent<SystemUtilization> = serde_wasm_bindgen::from_value(event).unwrap();
         curr.update(|history| {
-            history.push_back(values);
-            if history.len() > TIME_OPTIONS[TIME_OPTIONS.len() - 1] as usize {
+            history.push_back(event.payload);
+            if history.len() > TIME_OPTIONS[0] as usize {
                 history.pop_front();
             }
         });
     });
-    let t1 = js_sys::Date::now();
-    let elapsed = Duration::from_millis((t1 - t0) as u64);
-    set_timeout(
-        move || push_stats(curr, interval),
-        interval.saturating_sub(elapsed),
-    );
+    spawn_local(async move {
+        listen(STATS_EVENT, &handler).await;
+        // Listens for as long as the app runs
+        handler.forget();
+    });
 }
 
 #[component]
 pub fn App() -> impl IntoView {
-    let update_interval = Duration::from_millis(1000);
     let sys_util_history = RwSignal::new(VecDeque::new());
     let sys_info = RwSignal::new(SystemInfo::default());
     let main_view = RwSignal::new(MainView::Cpu);
@@ -799,22 +2121,20 @@ pub fn App() -> impl IntoView {
         sys_info.set(values);
     });
 
-    push_stats(sys_util_history.write_only(), update_interval);
-
-    const X_AXIS_LEN_STATIC: usize = TIME_OPTIONS[0] as usize;
-    let sys_util_history_side_panel = Signal::derive({
-        move || {
-            sys_util_history
-                .get()
-                .iter()
-                .rev()
-                .take(X_AXIS_LEN_STATIC)
-                .rev()
-                .cloned()
-                .collect()
+    // Replays a recording if the app was started with one, monitors the system otherwise
+    let replay_range = RwSignal::new(None::<(u64, u64)>);
+    spawn_local(async move {
+        let range = invoke("get_replay_range", JsValue::NULL).await;
+        match serde_wasm_bindgen::from_value(range).unwrap() {
+            Some(range) => replay_range.set(Some(range)),
+            None => subscribe_stats(sys_util_history.write_only()),
         }
     });
 
+    const X_AXIS_LEN_STATIC: usize = TIME_OPTIONS[0] as usize;
+    let sys_util_history_side_panel =
+        Signal::derive(move || sys_util_history.get().into_iter().collect());
+
     let (x_axis_points_static, _) = RwSignal::new(X_AXIS_LEN_STATIC).split();
 
     view! {
@@ -837,6 +2157,22 @@ pub fn App() -> impl IntoView {
 
                         </select>
                     </div>
+                    <ExportHistory
+                        sys_util_history=sys_util_history.read_only()
+                        history_time=history_time.read_only()
+                    />
+                    {move || {
+                        replay_range
+                            .get()
+                            .map(|range| {
+                                view! {
+                                    <ReplayControls
+                                        range=range
+                                        sys_util_history=sys_util_history.write_only()
+                                    />
+                                }
+                            })
+                    }}
                 </div>
                 <MainPanel
                     main_view=main_view.read_only()
@@ -844,48 +2180,9 @@ pub fn App() -> impl IntoView {
                     max_history=x_axis_points.read_only()
                     sys_info=sys_info.read_only()
                     history_time=history_time.read_only()
+                    replaying=Signal::derive(move || replay_range.get().is_some())
                 />
             </div>
         </main>
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::{print_bytes, print_secs};
-
-    #[test]
-    fn print_bytes_test() {
-        let test_cases = [
-            (0, "0.0 B"),
-            (1023, "1023.0 B"),
-            (1024, "1.0 KiB"),
-            (21_372_137, "20.4 MiB"),
-            (2_137_213_721_372_137, "1943.8 TiB"),
-        ];
-        for (input, expected) in test_cases {
-            assert_eq!(expected, print_bytes(input));
-        }
-    }
-
-    #[test]
-    fn print_time_test() {
-        let test_cases = [
-            (0, "0 s"),
-            (59, "59 s"),
-            (60, "1 min"),
-            (3 * 60, "3 min"),
-            (3 * 60 + 1, "3 min"),
-            (60, "1 min"),
-            (5 * 60, "5 min"),
-            (30 * 60, "30 min"),
-            (3 * 60 * 60, "3 h"),
-            (6 * 60 * 60, "6 h"),
-            (12 * 60 * 60, "12 h"),
-            (24 * 60 * 60, "24 h"),
-        ];
-        for (input, expected) in test_cases {
-            assert_eq!(expected, print_secs(input));
-        }
-    }
-}